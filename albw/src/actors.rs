@@ -34,6 +34,20 @@ impl Actors {
         self.archive.get_mut().add(actor)
     }
 
+    /// Hashes of the paths of every actor, SARC archives don't keep the paths themselves.
+    pub fn hashes(&self) -> Result<Vec<u32>> {
+        self.archive.get().hashes()
+    }
+
+    /// Copies the actors with the given path hashes from another scene's actors.
+    pub fn copy_from<I>(&mut self, other: &Actors, hashes: I) -> Result<()>
+    where
+        I: IntoIterator<Item = u32>,
+    {
+        self.dirty = true;
+        self.archive.get_mut().copy_from(other.archive.get(), hashes)
+    }
+
     pub fn into_archive(self) -> Option<File<Sarc>> {
         self.dirty.then(|| self.archive.map(Sarc::compress))
     }
@@ -15,8 +15,9 @@ impl<'a> Course<'a> {
         Self { game, id }
     }
 
-    pub fn language(&self) -> Result<Language> {
-        self.game.language(self.id)
+    /// Loads this course's text and flow archive in the given language.
+    pub fn language(&self, language: &str) -> Result<Language> {
+        self.game.language(self.id, language)
     }
 
     pub fn scene(&self, index: u16) -> Result<Scene> {
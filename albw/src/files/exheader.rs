@@ -5,6 +5,24 @@ use bytey::*;
 pub struct ExHeader([u8; LEN]);
 
 impl ExHeader {
+    /// Gets the program ID from the access control info.
+    pub fn get_program_id(&self) -> u64 {
+        unsafe { u64::from_slice_unchecked(&self.0[0x200..]) }
+    }
+
+    /// Whether the code binary in the ExeFS is compressed.
+    pub fn is_code_compressed(&self) -> bool {
+        self.0[FLAGS] & CODE_COMPRESSED != 0
+    }
+
+    pub fn set_code_compressed(&mut self, compressed: bool) {
+        if compressed {
+            self.0[FLAGS] |= CODE_COMPRESSED;
+        } else {
+            self.0[FLAGS] &= !CODE_COMPRESSED;
+        }
+    }
+
     pub fn get_text_address(&self) -> u32 {
         unsafe { u32::from_slice_unchecked(&self.0[0x10..]) }
     }
@@ -46,6 +64,8 @@ impl FromBytes<'_> for ExHeader {
 }
 
 pub const LEN: usize = 0x400;
+const FLAGS: usize = 0xD;
+const CODE_COMPRESSED: u8 = 1;
 
 #[cfg(test)]
 mod tests {
@@ -75,4 +95,13 @@ mod tests {
         assert_eq!(u32::try_from_slice(&exheader.0[0x28..0x2C])?.0, 0x123456);
         Ok(())
     }
+
+    #[test]
+    fn it_clears_code_compressed() {
+        let mut exheader = new();
+        exheader.0[0xD] = 0x3;
+        exheader.set_code_compressed(false);
+        assert!(!exheader.is_code_compressed());
+        assert_eq!(exheader.0[0xD], 0x2);
+    }
 }
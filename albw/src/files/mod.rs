@@ -1,25 +1,39 @@
 use bytey::*;
 use data_encoding::HEXUPPER;
-use log::{info, error};
+use log::{info, warn};
 use ring::digest::{Context, SHA256};
 use serde::Serialize;
-use std::{fs, io::{BufReader, prelude::*, stdin, stdout}, path::Path};
+use std::{fs, io::{self, BufReader, prelude::*, SeekFrom}, path::Path};
 
-use crate::{Error, Result};
+use crate::{Error, ErrorKind, Result};
 
 use self::{exheader::ExHeader, romfs::RomFs};
 
 pub mod byaml;
 pub mod exheader;
 pub mod msgbn;
+pub mod ncch;
 pub mod romfs;
 pub mod sarc;
+pub mod source;
+
+/// The kind of file the game's NCCH partition was found in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Container {
+    /// A cartridge image (`.3ds`).
+    Ncsd,
+    /// A bare NCCH partition (`.cxi`).
+    Ncch,
+    /// A decrypted installable archive (`.cia`).
+    Cia,
+}
 
 #[derive(Debug)]
 pub struct Cxi<R> {
     file: R,
     id: u64,
     offset: u32,
+    container: Container,
 }
 
 impl<R> Cxi<R>
@@ -30,6 +44,10 @@ impl<R> Cxi<R>
         self.id
     }
 
+    pub fn container(&self) -> Container {
+        self.container
+    }
+
     pub fn exheader(&mut self) -> Result<ExHeader> {
         Ok(ExHeader::read_from_offset(
             &mut self.file,
@@ -43,89 +61,158 @@ impl<R> Cxi<R>
     }
 }
 
-// FIXME unnecssary duplicate
-fn pause() {
-    let mut stdout = stdout();
-    stdout.write(b"\nPress Enter to continue...").unwrap();
-    stdout.flush().unwrap();
-    stdin().read(&mut [0]).unwrap();
-}
-
 impl Cxi<fs::File> {
-    pub fn open<P>(path: P) -> Result<Self>
+    /// Opens a decrypted ROM image, checking it against the known dumps if `verify` is set.
+    pub fn open<P>(path: P, verify: bool) -> Result<Self>
         where
             P: AsRef<Path>,
     {
         let path = path.as_ref();
         let mut file = match fs::File::open(path) {
             Ok(file) => file,
-            Err(_) => {
-                error!("Couldn't load ROM from: \"{}\"", path.display());
-                error!("Please check that config.toml points to a valid ROM.");
-                pause();
-                std::process::exit(1);
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(Error::with_kind(
+                    ErrorKind::RomNotFound,
+                    format!("Couldn't find a ROM at: \"{}\"", path.display()),
+                ));
             }
+            Err(err) => return Err(err.into()),
         };
 
-        validate_rom(&file);
-
-        bytey::typedef! { struct NCSD: TryFromBytes<'_> [HEADER_LEN] {
-            #b"NCSD",
-            [8] id: u64,
-            [0x20] offset: u32,
-        }}
-        let header = NCSD::try_read_from_offset(&mut file, SIGNATURE_LEN)?;
-        let offset = from_media_units(header.offset);
+        let (container, offset, id) = match read_magic(&mut file, SIGNATURE_LEN)? {
+            magic if &magic == b"NCSD" => {
+                bytey::typedef! { struct NCSD: TryFromBytes<'_> [HEADER_LEN] {
+                    #b"NCSD",
+                    [8] id: u64,
+                    [0x20] offset: u32,
+                }}
+                let header = NCSD::try_read_from_offset(&mut file, SIGNATURE_LEN)?;
+                (Container::Ncsd, from_media_units(header.offset), Some(header.id))
+            }
+            magic if &magic == b"NCCH" => (Container::Ncch, 0, None),
+            _ if u32::read_from_offset(&mut file, 0u32)? == CIA_HEADER_LEN => {
+                bytey::typedef! { struct CIA: FromBytes<'_> [0x18] {
+                    [0] header: u32,
+                    [8] certificates: u32,
+                    [0xC] ticket: u32,
+                    [0x10] tmd: u32,
+                }}
+                let header = CIA::read_from_offset(&mut file, 0u32)?;
+                let offset = cia_content_offset([header.header, header.certificates, header.ticket, header.tmd]);
+                if &read_magic(&mut file, offset as u64 + SIGNATURE_LEN)? != b"NCCH" {
+                    return Err(Error::with_kind(
+                        ErrorKind::Encrypted,
+                        "CIA contents are encrypted. Please decrypt the CIA before using the randomizer.",
+                    ));
+                }
+                (Container::Cia, offset, None)
+            }
+            _ => {
+                return Err(Error::with_kind(
+                    ErrorKind::Format,
+                    "Unrecognized ROM format. Please provide a decrypted .3ds, .cxi or .cia file.",
+                ))
+            }
+        };
         bytey::typedef! { struct NCCH: TryFromBytes<'_> [HEADER_LEN] {
             #b"NCCH",
             [8] id: u64,
             [0x18] program_id: u64,
         }}
         let ncch = NCCH::try_read_from_offset(&mut file, offset + SIGNATURE_LEN as u32)?;
-        cmp_id(ncch.id, header.id)?;
-        cmp_id(ncch.program_id, header.id)?;
+        cmp_id(ncch.program_id, ncch.id)?;
+        if let Some(id) = id {
+            cmp_id(ncch.id, id)?;
+        }
+        let flags = u8::read_from_offset(&mut file, offset + NCCH_CRYPTO_FLAGS)?;
+        if flags & NO_CRYPTO == 0 {
+            return Err(Error::with_kind(
+                ErrorKind::Encrypted,
+                "ROM is encrypted. Please decrypt this ROM before using the randomizer.",
+            ));
+        }
+        if verify {
+            verify_rom(&mut file, container)?;
+        } else {
+            info!("Skipping ROM checksum verification.");
+        }
         Ok(Self {
             file,
-            id: header.id,
+            id: ncch.id,
             offset,
+            container,
         })
     }
 }
 
-fn validate_rom(file: &fs::File) {
-    //info!("Calculating Checksum...");
+fn read_magic<R>(file: &mut R, offset: u64) -> Result<[u8; 4]>
+    where
+        R: Read + Seek,
+{
+    let mut magic = [0; 4];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut magic)?;
+    Ok(magic)
+}
 
+/// Finds the first content of a CIA, after its header, certificates, ticket and TMD, each aligned to 0x40 bytes.
+fn cia_content_offset(sections: [u32; 4]) -> u32 {
+    sections.iter().map(|&len| align::<0x40>(len)).sum()
+}
+
+/// A ROM dump whose checksum has been verified.
+struct KnownDump {
+    description: &'static str,
+    container: Container,
+    sha256: &'static str,
+}
+
+/// Every dump the randomizer has been verified with.
+///
+/// Encrypted and other-region dumps are caught by their NCCH flags and title ID before the checksum is compared, so
+/// only supported dumps are listed. Containers without an entry here skip the comparison.
+const KNOWN_DUMPS: &[KnownDump] = &[KnownDump {
+    description: "North America, decrypted .3ds",
+    container: Container::Ncsd,
+    sha256: "4071DC95F6948669C7A13D378509D5A224E167B77CF8FD2E163484BA9AF8B64D",
+}];
+
+/// Hashes the whole ROM and checks it against the known dumps of its container.
+fn verify_rom(file: &mut fs::File, container: Container) -> Result<()> {
+    let known = KNOWN_DUMPS.iter().filter(|dump| dump.container == container).collect::<Vec<_>>();
+    if known.is_empty() {
+        warn!("No known checksums for this ROM format, skipping verification.");
+        return Ok(());
+    }
+
+    file.seek(SeekFrom::Start(0))?;
     let mut reader = BufReader::new(file);
     let mut context = Context::new(&SHA256);
-    let mut buffer = [0; 1024];
-
+    let mut buffer = [0; 0x10000];
     loop {
-        let count = reader.read(&mut buffer).unwrap();
+        let count = reader.read(&mut buffer)?;
         if count == 0 {
             break;
         }
         context.update(&buffer[..count]);
     }
-
-    let checksum = &HEXUPPER.encode(context.finish().as_ref());
+    let checksum = HEXUPPER.encode(context.finish().as_ref());
     info!("SHA256 Checksum:                {}", checksum);
 
-    // let valid_checksum = String::from("4071DC95F6948669C7A13D378509D5A224E167B77CF8FD2E163484BA9AF8B64D");
-    // let encrypted_checksum = String::from("tbd"); // TODO determine this value
-    //
-    // if valid_checksum.eq(checksum) {
-    //     info!("ROM is valid.");
-    // } else {
-    //     if encrypted_checksum.eq(checksum) {
-    //         error!("ROM is encrypted. Please decrypt this ROM before using the randomizer.");
-    //     } else {
-    //         error!("Invalid checksum: {}", checksum);
-    //         error!("ROM is invalid. Please provide a decrypted, North American ROM of The Legend of Zelda: A Link Between Worlds.");
-    //     }
-    //
-    //     pause();
-    //     std::process::exit(1);
-    // }
+    match known.iter().find(|dump| dump.sha256 == checksum) {
+        Some(dump) => {
+            info!("ROM is valid:                   {}", dump.description);
+            Ok(())
+        }
+        None => Err(Error::with_kind(
+            ErrorKind::Checksum,
+            format!(
+                "ROM checksum {} doesn't match any known dump. If you're using a modified ROM on purpose, set \
+                 `verify_rom = false` in config.toml.",
+                checksum
+            ),
+        )),
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -274,5 +361,45 @@ pub(crate) const fn align<const ALIGN: u32>(value: u32) -> u32 {
 }
 
 const SIGNATURE_LEN: u64 = 0x100;
+const CIA_HEADER_LEN: u32 = 0x2020;
+const NCCH_CRYPTO_FLAGS: u32 = 0x18F;
+const NO_CRYPTO: u8 = 0x4;
 const HEADER_LEN: usize = 0x100;
 const MEDIA_UNIT_SHIFT: u8 = 9;
+const MEDIA_UNIT: u64 = 1 << MEDIA_UNIT_SHIFT;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ncch(flags: u8) -> Vec<u8> {
+        let mut data = vec![0; 0x200];
+        data[0x100..0x104].copy_from_slice(b"NCCH");
+        data[0x108..0x110].copy_from_slice(&0x00040000000EC300u64.to_le_bytes());
+        data[0x118..0x120].copy_from_slice(&0x00040000000EC300u64.to_le_bytes());
+        data[0x18F] = flags;
+        data
+    }
+
+    fn open(data: &[u8]) -> Result<Cxi<fs::File>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("rom.cxi");
+        fs::write(&path, data)?;
+        Cxi::open(&path, true)
+    }
+
+    #[test]
+    fn it_classifies_bad_roms() {
+        assert!(matches!(open(&[0; 0x200]).unwrap_err().kind(), ErrorKind::Format));
+        assert!(matches!(Cxi::open("missing.3ds", true).unwrap_err().kind(), ErrorKind::RomNotFound));
+        assert!(matches!(open(&ncch(0)).unwrap_err().kind(), ErrorKind::Encrypted));
+        let cxi = open(&ncch(NO_CRYPTO)).unwrap();
+        assert_eq!(cxi.container(), Container::Ncch);
+        assert_eq!(cxi.id(), 0x00040000000EC300);
+    }
+
+    #[test]
+    fn it_finds_cia_content() {
+        assert_eq!(cia_content_offset([0x2020, 0xA00, 0x350, 0xB34]), 0x3900);
+    }
+}
@@ -0,0 +1,314 @@
+//! Rebuilding a standalone game image around a patched code binary and RomFS.
+
+use std::{
+    fs,
+    io::{self, prelude::*, SeekFrom},
+    path::Path,
+};
+
+use ring::digest::{digest, SHA256};
+
+use super::{exheader::ExHeader, source::RomImage, Container, File, MEDIA_UNIT};
+use crate::{Error, Result};
+
+/// The container format of a rebuilt game image.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ImageFormat {
+    /// A full cartridge image (`.3ds`), keeping the manual and any other partitions.
+    Cci,
+    /// The game's executable content alone (`.cxi`).
+    Cxi,
+}
+
+/// Writes a copy of the ROM image to `out`, with the code patched and the given RomFS files replaced.
+///
+/// The code binary is stored decompressed, and every hash in the NCCH header is recomputed. Signatures are left as
+/// they were, so the image is only accepted by systems that don't verify them.
+pub fn rebuild<F>(
+    image: &mut RomImage,
+    out: &Path,
+    format: ImageFormat,
+    exheader: &ExHeader,
+    patch_code: F,
+    files: Vec<File<Box<[u8]>>>,
+) -> Result<()>
+where
+    F: FnOnce(&mut Vec<u8>),
+{
+    if format == ImageFormat::Cci && image.container != Container::Ncsd {
+        return Err(Error::new("A .3ds image can only be written from a .3ds ROM, use the cxi format instead."));
+    }
+    let ncch = image.offset as u64;
+    let mut input = fs::File::open(&image.path)?;
+    let mut output = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(out)?;
+
+    let base = match format {
+        ImageFormat::Cci => {
+            let header = read_at(&mut input, 0, ncch)?;
+            output.write_all(&header)?;
+            ncch
+        }
+        ImageFormat::Cxi => 0,
+    };
+
+    let mut header = read_at(&mut input, ncch, NCCH_HEADER_LEN)?;
+    let mut exheader_region = read_at(&mut input, ncch + NCCH_HEADER_LEN, EXHEADER_REGION_LEN)?;
+    let compressed = exheader_region[EXHEADER_FLAGS] & CODE_COMPRESSED != 0;
+    exheader_region[..exheader.as_ref().len()].copy_from_slice(exheader.as_ref());
+    exheader_region[EXHEADER_FLAGS] &= !CODE_COMPRESSED;
+    let exheader_hash = digest(&SHA256, &exheader_region[..exheader.as_ref().len()]);
+
+    let region = |header: &[u8], offset: usize| {
+        (
+            read_u32(header, offset) * MEDIA_UNIT,
+            read_u32(header, offset + 4) * MEDIA_UNIT,
+        )
+    };
+    let (plain_offset, plain_len) = region(&header, 0x190);
+    let plain = read_at(&mut input, ncch + plain_offset, plain_len)?;
+    let (logo_offset, logo_len) = region(&header, 0x198);
+    let logo = read_at(&mut input, ncch + logo_offset, logo_len)?;
+    let (exefs_offset, exefs_len) = region(&header, 0x1A0);
+    let exefs = read_at(&mut input, ncch + exefs_offset, exefs_len)?;
+    let exefs = rebuild_exefs(&exefs, compressed, patch_code)?;
+    let exefs_hash_region = (read_u32(&header, 0x1A8) * MEDIA_UNIT).min(exefs.len() as u64) as usize;
+
+    let mut cursor = NCCH_HEADER_LEN + EXHEADER_REGION_LEN;
+    let mut place = |data: &[u8], alignment: u64| -> Result<(u64, u64)> {
+        if data.is_empty() {
+            return Ok((0, 0));
+        }
+        let offset = align(cursor, alignment);
+        output.seek(SeekFrom::Start(base + offset))?;
+        output.write_all(data)?;
+        cursor = offset + align(data.len() as u64, MEDIA_UNIT);
+        Ok((offset, data.len() as u64))
+    };
+    let plain = place(&plain, MEDIA_UNIT)?;
+    let logo = place(&logo, MEDIA_UNIT)?;
+    let exefs_region = place(&exefs, MEDIA_UNIT)?;
+    let romfs_offset = align(cursor, ROMFS_ALIGN);
+    let romfs = image.romfs.rebuild(files, &mut output, base + romfs_offset)?;
+    let len = romfs_offset + romfs.len;
+
+    write_u32(&mut header, 0x104, len / MEDIA_UNIT);
+    header[0x160..0x180].copy_from_slice(exheader_hash.as_ref());
+    for (offset, (start, size)) in [(0x190, plain), (0x198, logo), (0x1A0, exefs_region)] {
+        write_u32(&mut header, offset, start / MEDIA_UNIT);
+        write_u32(&mut header, offset + 4, align(size, MEDIA_UNIT) / MEDIA_UNIT);
+    }
+    write_u32(&mut header, 0x1B0, romfs_offset / MEDIA_UNIT);
+    write_u32(&mut header, 0x1B4, romfs.len / MEDIA_UNIT);
+    write_u32(&mut header, 0x1B8, romfs.hash_region / MEDIA_UNIT);
+    header[0x1C0..0x1E0].copy_from_slice(digest(&SHA256, &exefs[..exefs_hash_region]).as_ref());
+    header[0x1E0..0x200].copy_from_slice(&romfs.hash);
+    output.seek(SeekFrom::Start(base))?;
+    output.write_all(&header)?;
+    output.write_all(&exheader_region)?;
+
+    if format == ImageFormat::Cci {
+        let mut ncsd = read_at(&mut input, 0, NCSD_HEADER_LEN.min(ncch))?;
+        let mut end = base + len;
+        write_u32(&mut ncsd, PARTITION_TABLE + 4, len / MEDIA_UNIT);
+        for partition in 1..PARTITION_COUNT {
+            let entry = PARTITION_TABLE + partition * 8;
+            let (offset, size) = region(&ncsd, entry);
+            if size == 0 {
+                continue;
+            }
+            let start = align(end, MEDIA_UNIT);
+            input.seek(SeekFrom::Start(offset))?;
+            output.seek(SeekFrom::Start(start))?;
+            io::copy(&mut (&mut input).take(size), &mut output)?;
+            write_u32(&mut ncsd, entry, start / MEDIA_UNIT);
+            end = start + size;
+        }
+        let end = align(end, MEDIA_UNIT);
+        output.set_len(end)?;
+        write_u32(&mut ncsd, 0x104, end / MEDIA_UNIT);
+        ncsd[0x160..0x180].copy_from_slice(exheader_hash.as_ref());
+        write_u32(&mut ncsd, CARD_INFO_FILLED_SIZE, end);
+        output.seek(SeekFrom::Start(0))?;
+        output.write_all(&ncsd)?;
+    }
+    Ok(())
+}
+
+/// Reads the decompressed code binary from the ROM image.
+pub fn read_code(image: &RomImage) -> Result<Vec<u8>> {
+    let ncch = image.offset as u64;
+    let mut input = fs::File::open(&image.path)?;
+    let header = read_at(&mut input, ncch, NCCH_HEADER_LEN)?;
+    let exheader = read_at(&mut input, ncch + NCCH_HEADER_LEN, EXHEADER_REGION_LEN)?;
+    let offset = read_u32(&header, 0x1A0) * MEDIA_UNIT;
+    let len = read_u32(&header, 0x1A4) * MEDIA_UNIT;
+    let exefs = read_at(&mut input, ncch + offset, len)?;
+    let (_, code) = read_exefs(&exefs)?
+        .into_iter()
+        .find(|(name, _)| name.starts_with(b".code\0"))
+        .ok_or_else(|| Error::new("ExeFS has no code binary."))?;
+    if exheader[EXHEADER_FLAGS] & CODE_COMPRESSED != 0 {
+        decompress(&code)
+    } else {
+        Ok(code)
+    }
+}
+
+/// Rebuilds the ExeFS with a decompressed, patched `.code` and fresh hashes for every file.
+fn rebuild_exefs<F>(exefs: &[u8], compressed: bool, patch_code: F) -> Result<Vec<u8>>
+where
+    F: FnOnce(&mut Vec<u8>),
+{
+    let mut files = read_exefs(exefs)?;
+    let mut patch_code = Some(patch_code);
+    for (name, data) in files.iter_mut() {
+        if name.starts_with(b".code\0") {
+            if compressed {
+                *data = decompress(data)?;
+            }
+            if let Some(patch_code) = patch_code.take() {
+                patch_code(data);
+            }
+        }
+    }
+
+    let mut buf = vec![0; EXEFS_HEADER_LEN];
+    for (i, (name, data)) in files.iter().enumerate() {
+        let offset = buf.len() - EXEFS_HEADER_LEN;
+        let entry = &mut buf[i * 0x10..(i + 1) * 0x10];
+        entry[..8].copy_from_slice(name);
+        entry[8..0xC].copy_from_slice(&(offset as u32).to_le_bytes());
+        entry[0xC..].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        let hash = EXEFS_HEADER_LEN - 0x20 * (i + 1);
+        buf[hash..hash + 0x20].copy_from_slice(digest(&SHA256, data).as_ref());
+        buf.extend_from_slice(data);
+        buf.resize(align(buf.len() as u64, MEDIA_UNIT) as usize, 0);
+    }
+    Ok(buf)
+}
+
+/// Lists the files in an ExeFS as pairs of their padded name and contents.
+fn read_exefs(exefs: &[u8]) -> Result<Vec<(&[u8], Vec<u8>)>> {
+    let mut files = vec![];
+    for entry in exefs[..EXEFS_HEADER_LEN].chunks_exact(0x10).take(EXEFS_FILE_COUNT) {
+        let name = &entry[..8];
+        let offset = EXEFS_HEADER_LEN + read_u32(entry, 8) as usize;
+        let len = read_u32(entry, 0xC) as usize;
+        if name.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        let data = exefs
+            .get(offset..offset + len)
+            .ok_or_else(|| Error::new("ExeFS file out of bounds."))?;
+        files.push((name, data.to_vec()));
+    }
+    Ok(files)
+}
+
+/// Decompresses a code binary packed with the backwards LZ77 scheme used for ExeFS code.
+pub(crate) fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    let invalid = || Error::new("Code binary could not be decompressed.");
+    let len = data.len();
+    if len < 8 {
+        return Err(invalid());
+    }
+    let bounds = read_u32(data, len - 8) as usize;
+    let extra = read_u32(data, len - 4) as usize;
+    let top = bounds & 0xFFFFFF;
+    let bottom = bounds >> 24;
+    if top > len || bottom > top {
+        return Err(invalid());
+    }
+    let mut out = data.to_vec();
+    out.resize(len + extra, 0);
+    let end = len - top;
+    let mut src = len - bottom;
+    let mut dst = len + extra;
+    while src > end {
+        src -= 1;
+        let flags = out[src];
+        for bit in 0..8 {
+            if flags << bit & 0x80 == 0 {
+                if src <= end || dst <= end {
+                    return Err(invalid());
+                }
+                src -= 1;
+                dst -= 1;
+                out[dst] = out[src];
+            } else {
+                if src < end + 2 {
+                    return Err(invalid());
+                }
+                src -= 2;
+                let segment = u16::from_le_bytes([out[src], out[src + 1]]) as usize;
+                let offset = (segment & 0xFFF) + 3;
+                let size = (segment >> 12) + 3;
+                if dst < end + size {
+                    return Err(invalid());
+                }
+                for _ in 0..size {
+                    dst -= 1;
+                    out[dst] = *out.get(dst + offset).ok_or_else(invalid)?;
+                }
+            }
+            if src <= end {
+                break;
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn read_at<R>(input: &mut R, offset: u64, len: u64) -> Result<Vec<u8>>
+where
+    R: Read + Seek,
+{
+    let mut buf = vec![0; len as usize];
+    input.seek(SeekFrom::Start(offset))?;
+    input.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u64 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap()) as u64
+}
+
+fn write_u32(buf: &mut [u8], offset: usize, value: u64) {
+    buf[offset..offset + 4].copy_from_slice(&(value as u32).to_le_bytes());
+}
+
+fn align(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+const NCSD_HEADER_LEN: u64 = 0x1000;
+const NCCH_HEADER_LEN: u64 = 0x200;
+const EXHEADER_REGION_LEN: u64 = 0x800;
+const EXHEADER_FLAGS: usize = 0xD;
+const CODE_COMPRESSED: u8 = 1;
+const EXEFS_HEADER_LEN: usize = 0x200;
+const EXEFS_FILE_COUNT: usize = 10;
+const ROMFS_ALIGN: u64 = 0x1000;
+const PARTITION_TABLE: usize = 0x120;
+const PARTITION_COUNT: usize = 8;
+const CARD_INFO_FILLED_SIZE: usize = 0x300;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_decompresses_code() {
+        // An uncompressed "P", then the literals "ABC" and an 18-byte copy from 3 bytes back, read from the end.
+        let mut data = b"P".to_vec();
+        data.extend_from_slice(&[0x00, 0xF0, b'A', b'B', b'C', 0x10]);
+        data.extend_from_slice(&(8u32 << 24 | 14).to_le_bytes());
+        data.extend_from_slice(&7u32.to_le_bytes());
+        assert_eq!(decompress(&data).unwrap(), [b"P".as_slice(), &b"ABC".repeat(7)].concat());
+    }
+}
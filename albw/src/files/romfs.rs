@@ -1,9 +1,10 @@
-use std::io::prelude::*;
+use std::io::{self, prelude::*, SeekFrom};
 
 use bytey::*;
+use ring::digest::{digest, SHA256};
 
-use super::File;
-use crate::{Error, Result};
+use super::{File, MEDIA_UNIT};
+use crate::{Error, ErrorKind, Result};
 
 #[derive(Debug)]
 pub struct RomFs<R> {
@@ -57,7 +58,7 @@ where
             };
             let (offset, length) = self
                 .find_file(parent, file)?
-                .ok_or_else(|| Error::new(format!("File not found: '{}'.", path)))?;
+                .ok_or_else(|| Error::with_kind(ErrorKind::NotFound, format!("File not found: '{}'.", path)))?;
             Ok(File::new(
                 path,
                 bytey::read_slice_from_offset(
@@ -69,6 +70,67 @@ where
         }
     }
 
+    /// Writes a copy of this RomFS to `out` at `base`, with the given files replaced or added.
+    pub fn rebuild<W>(&mut self, files: Vec<File<Box<[u8]>>>, out: &mut W, base: u64) -> Result<Rebuilt>
+    where
+        W: Read + Write + Seek,
+    {
+        let mut tree = Tree::default();
+        tree.dirs.push(Dir::default());
+        self.walk(&mut tree, 0, 0)?;
+        for file in files {
+            tree.insert(file);
+        }
+
+        write(tree, &mut self.file, out, base)
+    }
+
+    fn walk(&mut self, tree: &mut Tree, offset: u32, index: usize) -> Result<()> {
+        bytey::typedef! { struct DirMetadata: FromBytes<'_> [0x18] {
+            [4] sibling: u32,
+            [8] child: u32,
+            [0xC] file: u32,
+            [0x14] name_len: u32,
+        }}
+        bytey::typedef! { struct FileMetadata: FromBytes<'_> [0x20] {
+            [4] sibling: u32,
+            [8] offset: u64,
+            [0x10] length: u64,
+            [0x1C] name_len: u32,
+        }}
+        let metadata = DirMetadata::read_from_offset(&mut self.file, self.directories.metadata + offset)?;
+        let mut file = metadata.file;
+        while file != NONE {
+            let metadata = FileMetadata::read_from_offset(&mut self.file, self.files.metadata + file)?;
+            let name = read_name(&mut self.file, metadata.name_len)?;
+            tree.dirs[index].files.push(tree.files.len());
+            tree.files.push(Entry {
+                name,
+                parent: index,
+                source: Source::Original {
+                    offset: self.file_data as u64 + metadata.offset,
+                    len: metadata.length,
+                },
+            });
+            file = metadata.sibling;
+        }
+        let mut child = metadata.child;
+        while child != NONE {
+            let metadata = DirMetadata::read_from_offset(&mut self.file, self.directories.metadata + child)?;
+            let name = read_name(&mut self.file, metadata.name_len)?;
+            let child_index = tree.dirs.len();
+            tree.dirs[index].dirs.push(child_index);
+            tree.dirs.push(Dir {
+                name,
+                parent: index,
+                ..Default::default()
+            });
+            self.walk(tree, child, child_index)?;
+            child = metadata.sibling;
+        }
+        Ok(())
+    }
+
     fn find_dir(&mut self, name: Option<&str>, parent: u32) -> Result<u32> {
         if let Some(path) = name {
             let mut split = path.splitn(2, '/');
@@ -92,7 +154,7 @@ where
                     offset = metadata.next;
                 }
             }
-            Err(Error::new(format!("Directory not found: '{}'.", path)))
+            Err(Error::with_kind(ErrorKind::NotFound, format!("Directory not found: '{}'.", path)))
         } else {
             Ok(parent)
         }
@@ -124,6 +186,367 @@ where
     }
 }
 
+/// The size and superblock hash of a rebuilt RomFS, as recorded in its NCCH header.
+#[derive(Debug)]
+pub struct Rebuilt {
+    pub len: u64,
+    pub hash_region: u64,
+    pub hash: [u8; 0x20],
+}
+
+#[derive(Debug)]
+enum Source {
+    Original { offset: u64, len: u64 },
+    Replaced(Box<[u8]>),
+}
+
+impl Source {
+    fn len(&self) -> u64 {
+        match self {
+            Self::Original { len, .. } => *len,
+            Self::Replaced(data) => data.len() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Dir {
+    name: String,
+    parent: usize,
+    dirs: Vec<usize>,
+    files: Vec<usize>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    name: String,
+    parent: usize,
+    source: Source,
+}
+
+/// The file tree of a RomFS, laid out as Level 3 in index order with the root directory first.
+#[derive(Debug, Default)]
+struct Tree {
+    dirs: Vec<Dir>,
+    files: Vec<Entry>,
+}
+
+impl Tree {
+    fn insert(&mut self, file: File<Box<[u8]>>) {
+        let File { path, inner } = file;
+        let path = path.strip_prefix('/').unwrap_or(&path);
+        let mut split = path.rsplitn(2, '/');
+        let name = split.next().expect("Attempt to write empty file name.");
+        let mut dir = 0;
+        for dirname in split.next().into_iter().flat_map(|parent| parent.split('/')) {
+            dir = match self.dirs[dir].dirs.iter().find(|&&child| self.dirs[child].name == dirname) {
+                Some(&child) => child,
+                None => {
+                    let child = self.dirs.len();
+                    self.dirs[dir].dirs.push(child);
+                    self.dirs.push(Dir {
+                        name: dirname.to_string(),
+                        parent: dir,
+                        ..Default::default()
+                    });
+                    child
+                }
+            };
+        }
+        let source = Source::Replaced(inner);
+        match self.dirs[dir].files.iter().find(|&&file| self.files[file].name == name) {
+            Some(&file) => self.files[file].source = source,
+            None => {
+                self.dirs[dir].files.push(self.files.len());
+                self.files.push(Entry {
+                    name: name.to_string(),
+                    parent: dir,
+                    source,
+                });
+            }
+        }
+    }
+
+    fn dir_offsets(&self) -> Vec<u32> {
+        offsets(self.dirs.iter().map(|dir| 0x18 + name_len(&dir.name)))
+    }
+
+    fn file_offsets(&self) -> Vec<u32> {
+        offsets(self.files.iter().map(|file| 0x20 + name_len(&file.name)))
+    }
+
+    fn data_offsets(&self) -> Vec<u64> {
+        let mut offset = 0;
+        self.files
+            .iter()
+            .map(|file| {
+                let start = align(offset, 0x10);
+                offset = start + file.source.len();
+                start
+            })
+            .collect()
+    }
+
+    fn sections(&self) -> [u64; 5] {
+        let dir_hashtable = L3_HEADER_LEN as u64;
+        let dir_metadata = dir_hashtable + 4 * table_len(self.dirs.len()) as u64;
+        let file_hashtable = dir_metadata + self.dir_offsets().last().copied().unwrap_or(0) as u64;
+        let file_metadata = file_hashtable + 4 * table_len(self.files.len()) as u64;
+        let file_data = align(
+            file_metadata + self.file_offsets().last().copied().unwrap_or(0) as u64,
+            0x10,
+        );
+        [dir_hashtable, dir_metadata, file_hashtable, file_metadata, file_data]
+    }
+
+    fn len(&self) -> u64 {
+        let data_len = self
+            .data_offsets()
+            .last()
+            .zip(self.files.last())
+            .map(|(offset, file)| offset + file.source.len())
+            .unwrap_or(0);
+        self.sections()[4] + data_len
+    }
+
+    fn write<R, W>(&self, source: &mut R, out: &mut W, base: u64) -> Result<()>
+    where
+        R: Read + Seek,
+        W: Write + Seek,
+    {
+        let dir_offsets = self.dir_offsets();
+        let file_offsets = self.file_offsets();
+        let [dir_hashtable, dir_metadata, file_hashtable, file_metadata, file_data] = self.sections();
+
+        let mut dir_table = vec![NONE; table_len(self.dirs.len())];
+        let mut dir_buf = vec![];
+        for (i, dir) in self.dirs.iter().enumerate() {
+            let parent = dir_offsets[dir.parent];
+            let sibling = self.dirs[dir.parent]
+                .dirs
+                .iter()
+                .skip_while(|&&child| child != i)
+                .nth(1)
+                .map_or(NONE, |&next| dir_offsets[next]);
+            let bucket = (hash(&dir.name, parent) % dir_table.len() as u32) as usize;
+            for value in [
+                parent,
+                sibling,
+                dir.dirs.first().map_or(NONE, |&child| dir_offsets[child]),
+                dir.files.first().map_or(NONE, |&file| file_offsets[file]),
+                dir_table[bucket],
+            ] {
+                dir_buf.extend_from_slice(&value.to_le_bytes());
+            }
+            dir_table[bucket] = dir_offsets[i];
+            write_name(&mut dir_buf, &dir.name);
+        }
+
+        let data_offsets = self.data_offsets();
+        let mut file_table = vec![NONE; table_len(self.files.len())];
+        let mut file_buf = vec![];
+        for (i, file) in self.files.iter().enumerate() {
+            let parent = dir_offsets[file.parent];
+            let sibling = self.dirs[file.parent]
+                .files
+                .iter()
+                .skip_while(|&&other| other != i)
+                .nth(1)
+                .map_or(NONE, |&next| file_offsets[next]);
+            let bucket = (hash(&file.name, parent) % file_table.len() as u32) as usize;
+            file_buf.extend_from_slice(&parent.to_le_bytes());
+            file_buf.extend_from_slice(&sibling.to_le_bytes());
+            file_buf.extend_from_slice(&data_offsets[i].to_le_bytes());
+            file_buf.extend_from_slice(&file.source.len().to_le_bytes());
+            file_buf.extend_from_slice(&file_table[bucket].to_le_bytes());
+            file_table[bucket] = file_offsets[i];
+            write_name(&mut file_buf, &file.name);
+        }
+
+        let mut header = (L3_HEADER_LEN as u32).to_le_bytes().to_vec();
+        for (offset, len) in [
+            (dir_hashtable, 4 * dir_table.len()),
+            (dir_metadata, dir_buf.len()),
+            (file_hashtable, 4 * file_table.len()),
+            (file_metadata, file_buf.len()),
+        ] {
+            header.extend_from_slice(&(offset as u32).to_le_bytes());
+            header.extend_from_slice(&(len as u32).to_le_bytes());
+        }
+        header.extend_from_slice(&(file_data as u32).to_le_bytes());
+
+        out.seek(SeekFrom::Start(base))?;
+        out.write_all(&header)?;
+        for offset in dir_table {
+            out.write_all(&offset.to_le_bytes())?;
+        }
+        out.write_all(&dir_buf)?;
+        for offset in file_table {
+            out.write_all(&offset.to_le_bytes())?;
+        }
+        out.write_all(&file_buf)?;
+        for (file, offset) in self.files.iter().zip(data_offsets) {
+            pad(out, base + file_data + offset)?;
+            match &file.source {
+                Source::Original { offset, len } => {
+                    source.seek(SeekFrom::Start(*offset))?;
+                    io::copy(&mut source.take(*len), out)?;
+                }
+                Source::Replaced(data) => out.write_all(data)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a RomFS holding `tree` to `out` at `base`, copying original file data from `source`.
+fn write<R, W>(tree: Tree, source: &mut R, out: &mut W, base: u64) -> Result<Rebuilt>
+where
+    R: Read + Seek,
+    W: Read + Write + Seek,
+{
+    let l3_len = tree.len();
+    let l2_len = hash_len(l3_len);
+    let l1_len = hash_len(l2_len);
+    let master_len = hash_len(l1_len);
+    let l3 = base + align(IVFC_HEADER_LEN + master_len, BLOCK_LEN);
+    let l1 = align(l3 + l3_len, BLOCK_LEN);
+    let l2 = align(l1 + l1_len, BLOCK_LEN);
+    let end = align(l2 + l2_len, BLOCK_LEN);
+
+    tree.write(source, out, l3)?;
+    hash_level(out, l3, l3_len, l2)?;
+    hash_level(out, l2, l2_len, l1)?;
+    hash_level(out, l1, l1_len, base + IVFC_HEADER_LEN)?;
+
+    let l2_logical = align(l1_len, BLOCK_LEN);
+    let l3_logical = align(l2_logical + l2_len, BLOCK_LEN);
+    let mut header = vec![];
+    header.extend_from_slice(b"IVFC");
+    header.extend_from_slice(&0x10000u32.to_le_bytes());
+    header.extend_from_slice(&(master_len as u32).to_le_bytes());
+    for (logical, len) in [(0, l1_len), (l2_logical, l2_len), (l3_logical, l3_len)] {
+        header.extend_from_slice(&logical.to_le_bytes());
+        header.extend_from_slice(&len.to_le_bytes());
+        header.extend_from_slice(&BLOCK_SHIFT.to_le_bytes());
+        header.extend_from_slice(&[0; 4]);
+    }
+    header.extend_from_slice(&(HEADER_LEN as u32).to_le_bytes());
+    header.extend_from_slice(&[0; 4]);
+    out.seek(SeekFrom::Start(base))?;
+    out.write_all(&header)?;
+    out.seek(SeekFrom::Start(l2 + l2_len))?;
+    pad(out, end)?;
+
+    let hash_region = align(IVFC_HEADER_LEN + master_len, MEDIA_UNIT);
+    let mut superblock = vec![0; hash_region as usize];
+    out.seek(SeekFrom::Start(base))?;
+    out.read_exact(&mut superblock)?;
+    Ok(Rebuilt {
+        len: end - base,
+        hash_region,
+        hash: digest(&SHA256, &superblock).as_ref().try_into().unwrap(),
+    })
+}
+
+/// Hashes `len` bytes at `src` block by block, writing the hashes to `dst`.
+fn hash_level<F>(file: &mut F, src: u64, len: u64, dst: u64) -> Result<()>
+where
+    F: Read + Write + Seek,
+{
+    let mut hashes = Vec::with_capacity(hash_len(len) as usize);
+    let mut block = vec![0; BLOCK_LEN as usize];
+    file.seek(SeekFrom::Start(src))?;
+    let mut remaining = len;
+    while remaining > 0 {
+        let size = remaining.min(BLOCK_LEN) as usize;
+        block[size..].fill(0);
+        file.read_exact(&mut block[..size])?;
+        hashes.extend_from_slice(digest(&SHA256, &block).as_ref());
+        remaining -= size as u64;
+    }
+    file.seek(SeekFrom::Start(dst))?;
+    file.write_all(&hashes)?;
+    Ok(())
+}
+
+/// Zero-fills `out` from its current position up to `to`.
+fn pad<W>(out: &mut W, to: u64) -> Result<()>
+where
+    W: Write + Seek,
+{
+    let position = out.stream_position()?;
+    if to > position {
+        io::copy(&mut io::repeat(0).take(to - position), out)?;
+    }
+    Ok(())
+}
+
+fn read_name<R>(file: R, len: u32) -> Result<String>
+where
+    R: Read,
+{
+    let name = bytey::read_slice(file, len as usize)?;
+    let name = name
+        .chunks_exact(2)
+        .map(|ch| u16::from_le_bytes([ch[0], ch[1]]))
+        .collect::<Vec<_>>();
+    String::from_utf16(&name).map_err(|_| Error::new("Invalid RomFS file name."))
+}
+
+fn write_name(buf: &mut Vec<u8>, name: &str) {
+    let len = name_len(name);
+    buf.extend_from_slice(&(name.encode_utf16().count() as u32 * 2).to_le_bytes());
+    let start = buf.len();
+    buf.extend(name.encode_utf16().flat_map(u16::to_le_bytes));
+    buf.resize(start + len as usize, 0);
+}
+
+/// The length of an entry's name, padded to 4 bytes.
+fn name_len(name: &str) -> u32 {
+    (name.encode_utf16().count() as u32 * 2 + 3) & !3
+}
+
+fn offsets<I>(lens: I) -> Vec<u32>
+where
+    I: IntoIterator<Item = u32>,
+{
+    let mut offset = 0;
+    let mut offsets = lens
+        .into_iter()
+        .map(|len| {
+            let start = offset;
+            offset += len;
+            start
+        })
+        .collect::<Vec<_>>();
+    offsets.push(offset);
+    offsets
+}
+
+/// The number of hash table buckets for a given number of entries, as chosen by Nintendo's tools.
+fn table_len(count: usize) -> usize {
+    if count < 3 {
+        3
+    } else if count < 19 {
+        count | 1
+    } else {
+        let mut len = count;
+        while [2, 3, 5, 7, 11, 13, 17].iter().any(|&prime| len.is_multiple_of(prime)) {
+            len += 1;
+        }
+        len
+    }
+}
+
+fn hash_len(len: u64) -> u64 {
+    len.div_ceil(BLOCK_LEN) * 0x20
+}
+
+fn align(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
 bytey::typedef! { struct SectionHeader: FromBytes<'_> [0x10] {
     [0] hashtable_offset: u32,
     [4] hashtable_len: u32,
@@ -170,4 +593,51 @@ fn path_eq(this: &[u8], other: &str) -> bool {
 }
 
 const HEADER_LEN: usize = 0x5C;
+const IVFC_HEADER_LEN: u64 = 0x60;
 const L3_HEADER_LEN: usize = 0x28;
+const BLOCK_SHIFT: u32 = 12;
+const BLOCK_LEN: u64 = 1 << BLOCK_SHIFT;
+const NONE: u32 = 0xFFFFFFFF;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn file(path: &str, data: &[u8]) -> File<Box<[u8]>> {
+        File::new(path.to_string(), data.into())
+    }
+
+    #[test]
+    fn it_rebuilds() -> Result<()> {
+        let mut tree = Tree::default();
+        tree.dirs.push(Dir::default());
+        for (path, data) in [("a.bin", &b"first"[..]), ("World/b.bin", b"second"), ("World/Msg/c.bin", b"third")] {
+            tree.insert(file(path, data));
+        }
+        let mut original = Cursor::new(vec![]);
+        write(tree, &mut Cursor::new(vec![]), &mut original, 0)?;
+
+        let mut romfs = RomFs::load(original, 0)?;
+        let mut rebuilt = Cursor::new(vec![]);
+        let result = romfs.rebuild(
+            vec![file("World/b.bin", b"replaced"), file("World/Stage/d.bin", b"added")],
+            &mut rebuilt,
+            0,
+        )?;
+        assert_eq!(result.len, rebuilt.get_ref().len() as u64);
+
+        let mut romfs = RomFs::load(rebuilt, 0)?;
+        for (path, data) in [
+            ("a.bin", &b"first"[..]),
+            ("World/b.bin", b"replaced"),
+            ("World/Msg/c.bin", b"third"),
+            ("World/Stage/d.bin", b"added"),
+        ] {
+            assert_eq!(&*romfs.read(path)?.inner, data);
+        }
+        assert!(romfs.read("World/e.bin").is_err());
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use std::{
     cell::{Ref, RefCell},
     cmp::Ordering,
+    collections::BTreeMap,
     io::Cursor,
 };
 
@@ -46,15 +47,18 @@ impl Sarc {
         let path = path.into();
         debug!("Reading {} from archive", &path);
         let archive = self.decompress()?;
-        let (start, end) = archive
-            .find(&path)
+        let data = Ref::filter_map(archive, |archive| archive.get(&path).ok())
             .map_err(|_| Error::new(format!("File not found: '{}'.", path)))?;
-        let data = Ref::map(archive, |archive| {
-            &archive.files[start as usize..end as usize]
-        });
         Ok(File::new(path, data))
     }
 
+    /// Reads the file with the given path hash, for files whose path isn't known.
+    pub fn read_hashed(&self, hash: u32) -> Result<Ref<'_, [u8]>> {
+        let archive = self.decompress()?;
+        Ref::filter_map(archive, |archive| archive.get_hashed(hash))
+            .map_err(|_| Error::new(format!("File not found: {:08X}.", hash)))
+    }
+
     pub fn read_from_file<'a, T>(&'a self, args: &T::PathArgs) -> Result<File<T>>
     where
         T: FromFile<Input = Ref<'a, [u8]>>,
@@ -62,12 +66,8 @@ impl Sarc {
         let path = T::path(args);
         debug!("Reading {} from archive", &path);
         let archive = self.decompress()?;
-        let (start, end) = archive
-            .find(&path)
+        let input = Ref::filter_map(archive, |archive| archive.get(&path).ok())
             .map_err(|_| Error::new(format!("File not found: '{}'.", path)))?;
-        let input = Ref::map(archive, |archive| {
-            &archive.files[start as usize..end as usize]
-        });
         Ok(File::new(path, T::from_file(input)?))
     }
 
@@ -97,11 +97,10 @@ impl Sarc {
         T: FromFile<Input = &'s mut [u8]>,
     {
         let path = T::path(args);
-        let archive = self.decompress_mut()?;
-        let (start, end) = archive
-            .find(&path)
+        let input = self
+            .decompress_mut()?
+            .get_mut(&path)
             .map_err(|_| Error::new(format!("File not found: '{}'.", path)))?;
-        let input = &mut archive.files[start as usize..end as usize];
         Ok(File::new(path, T::from_file(input)?))
     }
 
@@ -110,6 +109,30 @@ impl Sarc {
         Ok(())
     }
 
+    /// Hashes of the paths of every file, which is all that's left of a path once it's in the archive.
+    pub fn hashes(&self) -> Result<Vec<u32>> {
+        Ok(self.decompress()?.hashes().collect())
+    }
+
+    /// Copies the files with the given path hashes from another archive, replacing any that are already here.
+    pub fn copy_from<I>(&mut self, other: &Sarc, hashes: I) -> Result<()>
+    where
+        I: IntoIterator<Item = u32>,
+    {
+        let other = other.decompress()?;
+        let archive = self.decompress_mut()?;
+        if archive.multiplier != other.multiplier {
+            return Err(Error::new("Archives hash their paths differently."));
+        }
+        for hash in hashes {
+            let data = other
+                .get_hashed(hash)
+                .ok_or_else(|| Error::new(format!("File not found: {:08X}.", hash)))?;
+            archive.insert(hash, data.into());
+        }
+        Ok(())
+    }
+
     pub fn compress(self) -> Self {
         match self.0.into_inner() {
             Inner::Compressed(data) => Self::from(data),
@@ -163,6 +186,8 @@ pub struct Archive {
     multiplier: u32,
     nodes: Vec<u8>,
     files: Vec<u8>,
+    /// Contents of added and replaced files, moved into the data section once the archive is written.
+    pending: BTreeMap<u32, Box<[u8]>>,
 }
 
 impl Archive {
@@ -190,6 +215,7 @@ impl Archive {
                 multiplier: sfat.multiplier,
                 nodes,
                 files,
+                pending: BTreeMap::new(),
             })
         } else {
             Err(Error::new("unimpl113".to_string()))
@@ -203,15 +229,15 @@ impl Archive {
     }
 
     fn get(&self, path: &str) -> Result<&[u8]> {
-        if let Ok((start, end)) = self.find(path) {
-            Ok(&self.files[start as usize..end as usize])
-        } else {
-            Err(Error::new("File not found."))
-        }
+        self.get_hashed(self.hash(path))
+            .ok_or_else(|| Error::new("File not found."))
     }
 
     fn get_mut(&mut self, path: &str) -> Result<&mut [u8]> {
-        if let Ok((start, end)) = self.find(path) {
+        let hash = self.hash(path);
+        if self.pending.contains_key(&hash) {
+            Ok(self.pending.get_mut(&hash).unwrap())
+        } else if let Ok((start, end)) = self.search_hash(hash) {
             Ok(&mut self.files[start as usize..end as usize])
         } else {
             Err(Error::new("File not found."))
@@ -220,33 +246,90 @@ impl Archive {
 
     fn add(&mut self, file: File<Box<[u8]>>) {
         debug!("Add {}", file.path);
-        match self.find(&file.path) {
-            Ok(_) => {
-                // TODO
-            }
-            Err(i) => {
-                let i = i as usize * 0x10;
-                let File { path, inner } = file;
-                let hash = self.hash(&path);
-                let mut buf: Vec<_> = inner.into();
-                let start = align::<0x80>(self.files.len() as u32);
-                self.files.resize(start as usize, 0);
-                let end = start + buf.len() as u32;
-                buf.resize(align::<0x80>(buf.len() as u32) as usize, 0);
-                self.files.append(&mut buf);
-                let mut node = vec![];
-                node.extend_from_slice(&hash.to_le_bytes());
-                node.extend_from_slice(&[0, 0, 0, 0]);
-                node.extend_from_slice(&start.to_le_bytes());
-                node.extend_from_slice(&end.to_le_bytes());
-                self.nodes.splice(i..i, node);
-                self.count += 1;
-            }
+        let hash = self.hash(&file.path);
+        self.insert(hash, file.inner);
+    }
+
+    fn insert(&mut self, hash: u32, data: Box<[u8]>) {
+        if let Err(i) = self.search_hash(hash) {
+            // New files have no place in the data section until it's rebuilt
+            let i = i as usize * 0x10;
+            let mut node = vec![];
+            node.extend_from_slice(&hash.to_le_bytes());
+            node.extend_from_slice(&[0, 0, 0, 0]);
+            node.extend_from_slice(&u32::MAX.to_le_bytes());
+            node.extend_from_slice(&u32::MAX.to_le_bytes());
+            self.nodes.splice(i..i, node);
+            self.count += 1;
+        }
+        self.pending.insert(hash, data);
+    }
+
+    /// Rebuilds the data section in its original order with every pending file in place, so a replaced file leaves
+    /// nothing of itself behind. Files keep the alignment they had, new ones are aligned to 0x80.
+    fn rebuild(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let mut order = self
+            .nodes
+            .chunks_exact(0x10)
+            .map(|node| u32::from_le_bytes([node[8], node[9], node[0xA], node[0xB]]))
+            .enumerate()
+            .map(|(index, start)| (start, index))
+            .collect::<Vec<_>>();
+        order.sort_unstable();
+        let mut files = vec![];
+        for (start, index) in order {
+            let node = &mut self.nodes[index * 0x10..(index + 1) * 0x10];
+            let hash = u32::from_le_bytes([node[0], node[1], node[2], node[3]]);
+            let contents = if let Some(data) = self.pending.get(&hash) {
+                data
+            } else {
+                let end = u32::from_le_bytes([node[0xC], node[0xD], node[0xE], node[0xF]]);
+                &self.files[start as usize..end as usize]
+            };
+            let alignment = if start == u32::MAX {
+                0x80
+            } else {
+                1 << start.trailing_zeros().min(7)
+            };
+            let start = (files.len() as u32 + alignment - 1) & !(alignment - 1);
+            files.resize(start as usize, 0);
+            files.extend_from_slice(contents);
+            let end = files.len() as u32;
+            node[8..0xC].copy_from_slice(&start.to_le_bytes());
+            node[0xC..0x10].copy_from_slice(&end.to_le_bytes());
+        }
+        files.resize(align::<0x80>(files.len() as u32) as usize, 0);
+        self.files = files;
+        self.pending.clear();
+    }
+
+    fn hashes(&self) -> impl Iterator<Item = u32> + '_ {
+        self.nodes
+            .chunks_exact(0x10)
+            .map(|node| u32::from_le_bytes([node[0], node[1], node[2], node[3]]))
+    }
+
+    fn get_hashed(&self, hash: u32) -> Option<&[u8]> {
+        if let Some(data) = self.pending.get(&hash) {
+            return Some(data);
         }
+        let (start, end) = self.search_hash(hash).ok()?;
+        Some(&self.files[start as usize..end as usize])
     }
 
     fn find(&self, path: &str) -> Result<(u32, u32), u16> {
-        self.search(self.hash(path), 0, self.count - 1)
+        self.search_hash(self.hash(path))
+    }
+
+    fn search_hash(&self, hash: u32) -> Result<(u32, u32), u16> {
+        if self.count == 0 {
+            Err(0)
+        } else {
+            self.search(hash, 0, self.count - 1)
+        }
     }
 
     fn search(&self, hash: u32, start: u16, end: u16) -> Result<(u32, u32), u16> {
@@ -278,6 +361,7 @@ impl Archive {
 
 impl IntoBytes for Archive {
     fn into_bytes(mut self) -> Box<[u8]> {
+        self.rebuild();
         let offset = (0x28 + self.nodes.len() as u32 + 0xFF) & !0xFF;
         let len = offset + self.files.len() as u32;
         let count = (self.nodes.len() / 0x10) as u16;
@@ -333,8 +417,58 @@ fn compress(data: &[u8]) -> Box<[u8]> {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    fn archive() -> Archive {
+        Archive {
+            count: 0,
+            multiplier: 0x65,
+            nodes: vec![],
+            files: vec![],
+            pending: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn it_replaces_files_without_leaving_old_data() {
+        let mut archive = archive();
+        archive.add(File::new("a.bin".to_string(), vec![1; 0x100].into()));
+        archive.add(File::new("b.bin".to_string(), vec![2; 0x10].into()));
+        archive.add(File::new("a.bin".to_string(), vec![3; 0x20].into()));
+        assert_eq!(archive.count, 2);
+        assert_eq!(archive.get("a.bin").unwrap(), &[3; 0x20][..]);
+        assert_eq!(archive.get("b.bin").unwrap(), &[2; 0x10][..]);
+
+        let mut archive = Archive::from(archive.into_bytes()).unwrap();
+        assert_eq!(archive.get("a.bin").unwrap(), &[3; 0x20][..]);
+        assert_eq!(archive.get("b.bin").unwrap(), &[2; 0x10][..]);
+        archive.rebuild();
+        assert_eq!(archive.files.len(), 0x100);
+    }
+
+    #[test]
+    fn it_keeps_the_alignment_of_each_file() {
+        let mut archive = archive();
+        let (a, b) = (archive.hash("a.bin"), archive.hash("b.bin"));
+        let mut nodes = [(a, 0u32, 0x10u32), (b, 0x10, 0x20)];
+        nodes.sort_unstable();
+        for (hash, start, end) in nodes {
+            archive.nodes.extend_from_slice(&hash.to_le_bytes());
+            archive.nodes.extend_from_slice(&[0, 0, 0, 0]);
+            archive.nodes.extend_from_slice(&start.to_le_bytes());
+            archive.nodes.extend_from_slice(&end.to_le_bytes());
+        }
+        archive.count = 2;
+        archive.files = [[1; 0x10], [2; 0x10]].concat();
+        archive.insert(a, vec![3; 0x18].into());
+        archive.rebuild();
+        assert_eq!(archive.search_hash(a), Ok((0, 0x18)));
+        assert_eq!(archive.search_hash(b), Ok((0x20, 0x30)));
+        assert_eq!(archive.get("b.bin").unwrap(), &[2; 0x10][..]);
+    }
 }
@@ -0,0 +1,199 @@
+//! The places the game's files can be loaded from.
+
+use std::{
+    fmt::Debug,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bytey::*;
+
+use super::{exheader, exheader::ExHeader, ncch, romfs::RomFs, Container, Cxi, File};
+use crate::{Error, ErrorKind, ImageFormat, Result};
+
+/// A source of the game's files, with the same API however they are stored.
+pub trait FileSource: Debug {
+    /// Gets the 64-bit title ID.
+    fn id(&self) -> u64;
+
+    /// Gets the extended header.
+    fn exheader(&self) -> &ExHeader;
+
+    /// Reads a file from the RomFS.
+    fn read(&mut self, path: &str) -> Result<File<Box<[u8]>>>;
+
+    /// Reads the decompressed code binary.
+    fn code(&mut self) -> Result<Vec<u8>>;
+
+    /// Writes a standalone copy of the game, with its code patched and the given RomFS files replaced.
+    ///
+    /// Only sources holding a complete image can do this.
+    fn write_image(
+        &mut self,
+        _path: &Path,
+        _format: ImageFormat,
+        _exheader: &ExHeader,
+        _patch_code: &dyn Fn(&mut Vec<u8>),
+        _files: Vec<File<Box<[u8]>>>,
+    ) -> Result<()> {
+        Err(Error::new("A game image can only be written from a .3ds, .cxi or .cia file."))
+    }
+}
+
+/// A decrypted ROM image: a `.3ds` cartridge image, a `.cxi` partition or a `.cia` archive.
+#[derive(Debug)]
+pub struct RomImage {
+    pub(crate) path: PathBuf,
+    pub(crate) id: u64,
+    pub(crate) offset: u32,
+    pub(crate) container: Container,
+    pub(crate) exheader: ExHeader,
+    pub(crate) romfs: RomFs<fs::File>,
+}
+
+impl RomImage {
+    /// Opens a ROM image, checking it against the known dumps if `verify` is set.
+    pub fn open<P>(path: P, verify: bool) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let mut cxi = Cxi::open(&path, verify)?;
+        let id = cxi.id();
+        let offset = cxi.offset;
+        let container = cxi.container();
+        let exheader = cxi.exheader()?;
+        let romfs = cxi.try_into_romfs()?;
+        Ok(Self {
+            path,
+            id,
+            offset,
+            container,
+            exheader,
+            romfs,
+        })
+    }
+}
+
+impl FileSource for RomImage {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn exheader(&self) -> &ExHeader {
+        &self.exheader
+    }
+
+    fn read(&mut self, path: &str) -> Result<File<Box<[u8]>>> {
+        self.romfs.read(path)
+    }
+
+    fn code(&mut self) -> Result<Vec<u8>> {
+        ncch::read_code(self)
+    }
+
+    fn write_image(
+        &mut self,
+        path: &Path,
+        format: ImageFormat,
+        exheader: &ExHeader,
+        patch_code: &dyn Fn(&mut Vec<u8>),
+        files: Vec<File<Box<[u8]>>>,
+    ) -> Result<()> {
+        ncch::rebuild(self, path, format, exheader, patch_code, files)
+    }
+}
+
+/// An extracted dump: a directory holding the RomFS under `romfs/` and the extended header as `exheader.bin`.
+///
+/// The code binary is read from `exefs/code.bin`, which is only needed for some output formats.
+#[derive(Debug)]
+pub struct Extracted {
+    path: PathBuf,
+    exheader: ExHeader,
+}
+
+impl Extracted {
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        if !path.join("romfs").is_dir() {
+            return Err(Error::with_kind(
+                ErrorKind::RomNotFound,
+                format!("No romfs folder found in: \"{}\"", path.display()),
+            ));
+        }
+        let data = fs::read(path.join("exheader.bin")).map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => {
+                Error::with_kind(ErrorKind::RomNotFound, format!("No exheader.bin found in: \"{}\"", path.display()))
+            }
+            _ => err.into(),
+        })?;
+        let exheader = data
+            .get(..exheader::LEN)
+            .and_then(|data| data.try_into().ok())
+            .map(ExHeader::from_bytes)
+            .ok_or_else(|| Error::new("exheader.bin is too short."))?;
+        Ok(Self { path, exheader })
+    }
+}
+
+impl FileSource for Extracted {
+    fn id(&self) -> u64 {
+        self.exheader.get_program_id()
+    }
+
+    fn exheader(&self) -> &ExHeader {
+        &self.exheader
+    }
+
+    fn read(&mut self, path: &str) -> Result<File<Box<[u8]>>> {
+        let path = path.strip_prefix('/').unwrap_or(path);
+        match fs::read(self.path.join("romfs").join(path)) {
+            Ok(data) => Ok(File::new(path.to_string(), data.into())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                Err(Error::with_kind(ErrorKind::NotFound, format!("File not found: '{}'.", path)))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Reads `exefs/code.bin`, decompressing it if the dumping tool left it compressed.
+    ///
+    /// Tools differ on whether they decompress the code, so it's taken as already decompressed if it's big enough to
+    /// hold the text and read-only data segments.
+    fn code(&mut self) -> Result<Vec<u8>> {
+        let code = fs::read(self.path.join("exefs").join("code.bin"))?;
+        let decompressed_len = self.exheader.get_text_size() + self.exheader.get_rodata_size();
+        if self.exheader.is_code_compressed() && code.len() < decompressed_len as usize {
+            ncch::decompress(&code)
+        } else {
+            Ok(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_extracted_dumps() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir_all(dir.path().join("romfs/World"))?;
+        fs::write(dir.path().join("romfs/World/Test.txt"), b"test")?;
+        let mut exheader = vec![0; 0x800];
+        exheader[0x200..0x208].copy_from_slice(&0x00040000000EC300u64.to_le_bytes());
+        fs::write(dir.path().join("exheader.bin"), exheader)?;
+
+        let mut source = Extracted::open(dir.path())?;
+        assert_eq!(source.id(), 0x00040000000EC300);
+        let file = source.read("World/Test.txt")?;
+        assert_eq!(file.path(), "World/Test.txt");
+        assert_eq!(file.get().as_ref(), b"test");
+        assert!(matches!(source.read("World/Missing.txt").unwrap_err().kind(), ErrorKind::NotFound));
+        Ok(())
+    }
+}
@@ -70,7 +70,9 @@ impl<'flow, 'input> Steps<'flow, 'input> {
 
 #[derive(Debug)]
 pub enum Step {
+    /// Shows a message, by its index in the message file sharing the flow's name.
     Text {
+        message: u16,
         next: Next,
     },
     Branch {
@@ -102,7 +104,11 @@ impl Step {
         let step = Inner::from_bytes(bytes);
         let next = next(step.next);
         match step.kind {
-            1 => Ok(Self::Text { next }),
+            // The message index is the second parameter, the first names a message file but is always the flow's own
+            1 => Ok(Self::Text {
+                message: (step.value >> 16) as u16,
+                next,
+            }),
             2 => Self::branch(
                 branches,
                 Branch::new(step.command, step.value),
@@ -11,7 +11,8 @@ use crate::{
     course,
     files::{sarc::Sarc, FromFile},
     flow::{Flow, FlowMut},
-    Error, File, Result,
+    msbt::Msbt,
+    Error, File, IntoBytes, Result,
 };
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -75,6 +76,32 @@ impl Language {
         LoadedMut::new(&self.flow, &mut self.archive)
     }
 
+    /// Reads the message file with the given name.
+    pub fn msbt(&self, name: &str) -> Result<File<Msbt>> {
+        self.archive.get().read(Msbt::path(name))?.try_map(|data| Msbt::try_from_bytes(&data))
+    }
+
+    /// Reads every message file, including those whose name isn't known.
+    pub fn messages(&self) -> Result<Vec<Msbt>> {
+        let archive = self.archive.get();
+        let mut messages = vec![];
+        for hash in archive.hashes()? {
+            let data = archive.read_hashed(hash)?;
+            if Msbt::is_msbt(&data) {
+                messages.push(Msbt::try_from_bytes(&data)?);
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Writes a message file back into the archive, replacing the original.
+    pub fn update<T>(&mut self, file: File<T>) -> Result<()>
+    where
+        T: IntoBytes,
+    {
+        self.archive.get_mut().add(file.into_bytes())
+    }
+
     pub fn into_archive(self) -> File<Sarc> {
         self.archive.map(Sarc::compress)
     }
@@ -119,6 +146,10 @@ where
             .contains(name)
             .then(|| self.archive.get().read_from_file(name))
     }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.set.iter().map(String::as_str)
+    }
 }
 
 #[derive(Debug)]
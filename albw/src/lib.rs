@@ -4,8 +4,8 @@ use std::{
     cell::RefCell,
     error::Error as StdError,
     fmt::{self, Display, Formatter},
-    fs, io,
-    path::{Path},
+    io,
+    path::Path,
 };
 
 use log::info;
@@ -17,17 +17,22 @@ mod files;
 pub mod flow;
 mod item;
 pub mod language;
+pub mod msbt;
+pub mod region;
 pub mod scene;
 
 use actors::{Actor, Actors};
 pub use course::Course;
 pub use demo::Demo;
 pub use files::exheader::ExHeader;
-use files::{byaml, romfs::RomFs, sarc::Sarc, Cxi};
+pub use files::ncch::ImageFormat;
+use files::{byaml, sarc::Sarc};
+pub use files::source::{Extracted, FileSource, RomImage};
 pub use files::{File, IntoBytes};
 use item::GetItem;
 pub use item::Item;
 pub use language::Language;
+pub use region::Region;
 use language::{FlowChart};
 pub use scene::{Scene, Stage};
 use crate::language::Load;
@@ -43,11 +48,18 @@ pub struct Error {
 
 impl Error {
     fn new<T>(err: T) -> Self
+    where
+        T: Into<Box<dyn StdError + Send + Sync + 'static>>,
+    {
+        Self::with_kind(ErrorKind::Rom, err)
+    }
+
+    fn with_kind<T>(kind: ErrorKind, err: T) -> Self
     where
         T: Into<Box<dyn StdError + Send + Sync + 'static>>,
     {
         Self {
-            kind: ErrorKind::Rom,
+            kind,
             inner: err.into(),
         }
     }
@@ -81,18 +93,30 @@ impl Display for Error {
 impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match self.kind {
-            ErrorKind::Rom => None,
             ErrorKind::Io => Some(self.inner.as_ref()),
+            _ => None,
         }
     }
 }
 
 /// The kind of error contained within the `Error` type.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ErrorKind {
     /// An error within the provided ROM
     Rom,
     Io,
+    /// There's no ROM at the given path.
+    RomNotFound,
+    /// The ROM isn't in a format that can be read.
+    Format,
+    /// The ROM is still encrypted.
+    Encrypted,
+    /// The ROM is from another region, or isn't this game at all.
+    Region,
+    /// The ROM doesn't match any known dump, and may have been modified.
+    Checksum,
+    /// A requested file doesn't exist in the ROM.
+    NotFound,
 }
 
 /// Game info, loaded from a ROM
@@ -100,8 +124,9 @@ pub enum ErrorKind {
 #[allow(dead_code)]
 pub struct Game {
     id: u64,
+    region: &'static Region,
     exheader: ExHeader,
-    romfs: RefCell<RomFs<fs::File>>,
+    source: RefCell<Box<dyn FileSource>>,
     flow_chart: File<FlowChart>,
     get_item: File<Vec<GetItem>>,
     message: File<Load>,
@@ -110,81 +135,144 @@ pub struct Game {
 impl Game {
     /// Loads the game from a ROM.
     ///
+    /// The path may be a decrypted `.3ds`, `.cxi` or `.cia` file, or a directory holding an extracted `romfs` folder
+    /// and `exheader.bin`.
+    ///
     /// Fails if the ROM is invalid for any reason, including general
     /// corruption, mismatched IDs, invalid region, etc.
     pub fn load<P>(path: P) -> Result<Self>
     where
         P: AsRef<Path>,
     {
-        let path = path.as_ref().to_path_buf();
+        Self::load_from(path.as_ref(), true)
+    }
+
+    /// Loads the game from a ROM without checking it against the known dumps, to allow modified base ROMs.
+    pub fn load_unverified<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::load_from(path.as_ref(), false)
+    }
+
+    fn load_from(path: &Path, verify: bool) -> Result<Self> {
         info!("Loading ROM from:               {}", path.display());
-        let mut cxi = Cxi::open(&path)?;
-        if cxi.id() == US_ID {
-            let id = cxi.id();
-            let exheader = cxi.exheader()?;
-            let mut romfs = cxi.try_into_romfs()?;
-            let region_boot = romfs.read("US/RegionBoot.szs")?.map(Sarc::from);
-            let flow_chart = region_boot
-                .get()
-                .read("World/Byaml/FlowChart.byaml")?
-                .try_map(|data| byaml::from_bytes(&data))?;
-            let get_item = region_boot
-                .get()
-                .read("World/Byaml/GetItem.byaml")?
-                .try_map(|data| byaml::from_bytes(&data))?;
-            let message = region_boot
-                .get()
-                .read("World/Byaml/Message.byaml")?
-                .try_map(|data| byaml::from_bytes(&data))?;
-            Ok(Self {
-                id,
-                exheader,
-                romfs: RefCell::new(romfs),
-                flow_chart,
-                get_item,
-                message,
-            })
+        if path.is_dir() {
+            Self::from_source(Extracted::open(path)?)
         } else {
-            Err(Error::new("Invalid ROM ID."))
+            Self::from_source(RomImage::open(path, verify)?)
         }
     }
 
+    /// Loads the game from any source of its files.
+    pub fn from_source<S>(source: S) -> Result<Self>
+    where
+        S: FileSource + 'static,
+    {
+        let id = source.id();
+        let region = Region::from_id(id).ok_or_else(|| {
+            let supported = Region::iter()
+                .map(|region| format!("{} ({:016X})", region.name, region.id))
+                .collect::<Vec<_>>();
+            Error::with_kind(
+                ErrorKind::Region,
+                format!(
+                    "ROM has title ID {:016X}, but only the {} release is supported.",
+                    id,
+                    supported.join(", ")
+                ),
+            )
+        })?;
+        info!("Region:                         {}", region.name);
+        let exheader = source.exheader().clone();
+        let mut source: Box<dyn FileSource> = Box::new(source);
+        let region_boot = source.read(&region.boot_path())?.map(Sarc::from);
+        let flow_chart = region_boot
+            .get()
+            .read("World/Byaml/FlowChart.byaml")?
+            .try_map(|data| byaml::from_bytes(&data))?;
+        let get_item = region_boot
+            .get()
+            .read("World/Byaml/GetItem.byaml")?
+            .try_map(|data| byaml::from_bytes(&data))?;
+        let message = region_boot
+            .get()
+            .read("World/Byaml/Message.byaml")?
+            .try_map(|data| byaml::from_bytes(&data))?;
+        Ok(Self {
+            id,
+            region,
+            exheader,
+            source: RefCell::new(source),
+            flow_chart,
+            get_item,
+            message,
+        })
+    }
+
     /// Gets the 64-bit title ID.
     pub fn id(&self) -> u64 {
         self.id
     }
 
+    /// Gets the release this ROM is from.
+    pub fn region(&self) -> &'static Region {
+        self.region
+    }
+
     /// Gets the ROM's extended header.
     pub fn exheader(&self) -> &ExHeader {
         &self.exheader
     }
 
+    /// Reads the game's decompressed code binary.
+    pub fn code(&self) -> Result<Vec<u8>> {
+        self.source.borrow_mut().code()
+    }
+
+    /// Writes a standalone copy of this game's ROM, with its code patched and the given RomFS files replaced.
+    pub fn write_image<P, F>(
+        &self,
+        path: P,
+        format: ImageFormat,
+        exheader: &ExHeader,
+        patch_code: F,
+        files: Vec<File<Box<[u8]>>>,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+        F: Fn(&mut Vec<u8>),
+    {
+        self.source
+            .borrow_mut()
+            .write_image(path.as_ref(), format, exheader, &patch_code, files)
+    }
+
+    /// Reads a file from the RomFS.
+    fn read<P>(&self, path: P) -> Result<File<Box<[u8]>>>
+    where
+        P: AsRef<str>,
+    {
+        self.source.borrow_mut().read(path.as_ref())
+    }
+
     pub fn get_item(&self) -> impl Iterator<Item = (Item, GetItem)> + '_ {
         Item::iter().zip(self.get_item.get()[1..].iter().cloned())
     }
 
     fn get_item_actor(&self, name: &str) -> Result<Actor> {
-        self.romfs
-            .borrow_mut()
-            .read(format!("World/GetItem/{}.bch", name))
+        self.read(format!("World/GetItem/{}.bch", name))
     }
 
     pub fn boot(&self) -> Result<Language> {
         let flow = self.flow_chart.get().load().boot()?.iter().cloned();
-        let archive = self
-            .romfs
-            .borrow_mut()
-            .read("US/RegionBoot.szs")?
-            .map(Sarc::from);
+        let archive = self.read(self.region.boot_path())?.map(Sarc::from);
         Ok(Language::new(flow, archive))
     }
 
     pub fn common(&mut self) -> Result<Actors> {
         Ok(Actors::new(
-            self.romfs
-                .borrow_mut()
-                .read("Archive/ActorCommon.szs")?
-                .map(Sarc::from),
+            self.read("Archive/ActorCommon.szs")?.map(Sarc::from),
         ))
     }
 
@@ -193,13 +281,11 @@ impl Game {
     }
 
     pub fn demo(&self, index: u16) -> Result<File<Demo>> {
-        self.romfs
-            .borrow_mut()
-            .read(format!("World/Demo/Demo{}.csv", index + 1))?
+        self.read(format!("World/Demo/Demo{}.csv", index + 1))?
             .try_map(Demo::try_read)
     }
 
-    pub(crate) fn language(&self, course: course::Id) -> Result<Language> {
+    pub(crate) fn language(&self, course: course::Id, language: &str) -> Result<Language> {
         let flow = self
             .flow_chart
             .get()
@@ -208,29 +294,22 @@ impl Game {
             .unwrap_or_default()
             .iter()
             .cloned();
-        let archive = self
-            .romfs
-            .borrow_mut()
-            .read(format!("US_English/{}.szs", course.as_str()))?
-            .map(Sarc::from);
+        let archive = self.read(self.region.language_path(course, language))?.map(Sarc::from);
         Ok(Language::new(flow, archive))
     }
 
     pub(crate) fn scene(&self, course: course::Id, stage: u16) -> Result<Scene> {
         let name = format!("{}{}", course.as_str(), stage + 1);
-        let mut romfs = self.romfs.borrow_mut();
-        let stage = romfs
+        let stage = self
             .read(format!("World/Byaml/{}_stage.byaml", name))?
             .try_map(|data| byaml::from_bytes(&data))?;
-        let actors = romfs.read(format!("Archive/{}.szs", name))?.map(Sarc::from);
+        let actors = self.read(format!("Archive/{}.szs", name))?.map(Sarc::from);
         Ok(Scene::new(stage, actors))
     }
 
     pub(crate) fn stage(&self, course: course::Id, stage: u16) -> Result<Stage> {
         byaml::from_bytes(
-            self.romfs
-                .borrow_mut()
-                .read(format!(
+            self.read(format!(
                     "World/Byaml/{}{}_stage.byaml",
                     course.as_str(),
                     stage + 1
@@ -240,8 +319,6 @@ impl Game {
     }
 }
 
-const US_ID: u64 = 0x00040000000EC300;
-
 #[doc(hidden)]
 #[macro_export]
 macro_rules! int_map {
@@ -0,0 +1,577 @@
+//! Message Studio text files (MSBT), holding the dialogue and names shown in-game.
+
+use std::{
+    fmt::{self, Display, Formatter, Write},
+    str::FromStr,
+};
+
+use crate::{
+    files::{align, IntoBytes},
+    Error, Result,
+};
+
+/// A parsed MSBT file.
+///
+/// Only the UTF-16 encoding used by the game is supported. Sections other than labels, attributes and text are kept
+/// as-is so the file can be written back unchanged.
+#[derive(Clone, Debug)]
+pub struct Msbt {
+    /// The original header, written back with only the section count and file size updated.
+    header: [u8; HEADER_LEN],
+    sections: Vec<Section>,
+}
+
+#[derive(Clone, Debug)]
+enum Section {
+    Labels(Labels),
+    Attributes(Attributes),
+    Text(Vec<Message>),
+    Other([u8; 4], Box<[u8]>),
+}
+
+/// The LBL1 section, naming each message.
+#[derive(Clone, Debug)]
+struct Labels {
+    groups: u32,
+    labels: Vec<(String, u32)>,
+}
+
+/// The ATR1 section, a fixed-size attribute entry per message followed by any data the entries point into.
+#[derive(Clone, Debug)]
+struct Attributes {
+    size: u32,
+    entries: Vec<Box<[u8]>>,
+    extra: Box<[u8]>,
+}
+
+impl Msbt {
+    /// The archive path of the message file with the given name.
+    pub fn path(name: &str) -> String {
+        format!("World/Msg/{}.msbt", name)
+    }
+
+    /// Whether the data starts like a message file, for telling them apart from other files in an archive.
+    pub fn is_msbt(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self> {
+        let header = bytes.get(..HEADER_LEN).ok_or_else(|| Error::new("Ran out of data."))?;
+        if &header[..8] != MAGIC {
+            return Err(Error::new(format!("Expected MSBT magic number, found {:X?}.", &header[..8])));
+        }
+        if header[8..0xA] != [0xFF, 0xFE] {
+            return Err(Error::new("Only little-endian MSBT files are supported."));
+        }
+        if header[0xC] != UTF_16 {
+            return Err(Error::new(format!("Unsupported MSBT encoding: {}.", header[0xC])));
+        }
+        if read_u32(header, 0x12)? as usize != bytes.len() {
+            return Err(Error::new("Size did not match."));
+        }
+        let count = u16::from_le_bytes([header[0xE], header[0xF]]);
+
+        let mut sections = Vec::with_capacity(count as usize);
+        let mut index = HEADER_LEN;
+        for _ in 0..count {
+            let section_header = bytes
+                .get(index..index + SECTION_HEADER_LEN)
+                .ok_or_else(|| Error::new("Ran out of data."))?;
+            let magic = <[u8; 4]>::try_from(&section_header[..4]).unwrap();
+            let size = read_u32(section_header, 4)? as usize;
+            let start = index + SECTION_HEADER_LEN;
+            let data = bytes.get(start..start + size).ok_or_else(|| Error::new("Ran out of data."))?;
+            sections.push(match &magic {
+                LBL1 => Section::Labels(Labels::parse(data)?),
+                ATR1 => Section::Attributes(Attributes::parse(data)?),
+                TXT2 => Section::Text(parse_text(data)?),
+                _ => Section::Other(magic, data.into()),
+            });
+            index = start + align::<0x10>(size as u32) as usize;
+        }
+
+        Ok(Self {
+            header: header.try_into().unwrap(),
+            sections,
+        })
+    }
+
+    /// Iterates over every label along with the index of the message it names.
+    pub fn labels(&self) -> impl Iterator<Item = (&str, u32)> + '_ {
+        self.label_section()
+            .into_iter()
+            .flat_map(|labels| labels.labels.iter().map(|(label, index)| (label.as_str(), *index)))
+    }
+
+    /// All messages, in file order.
+    pub fn messages(&self) -> &[Message] {
+        self.sections
+            .iter()
+            .find_map(|section| match section {
+                Section::Text(messages) => Some(messages.as_slice()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn messages_mut(&mut self) -> &mut [Message] {
+        self.sections
+            .iter_mut()
+            .find_map(|section| match section {
+                Section::Text(messages) => Some(messages.as_mut_slice()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, label: &str) -> Option<&Message> {
+        let index = self.index_of(label)?;
+        self.messages().get(index as usize)
+    }
+
+    pub fn get_mut(&mut self, label: &str) -> Option<&mut Message> {
+        let index = self.index_of(label)?;
+        self.sections.iter_mut().find_map(|section| match section {
+            Section::Text(messages) => messages.get_mut(index as usize),
+            _ => None,
+        })
+    }
+
+    /// The raw attribute entry of the message with the given label.
+    pub fn attribute(&self, label: &str) -> Option<&[u8]> {
+        let index = self.index_of(label)?;
+        self.sections.iter().find_map(|section| match section {
+            Section::Attributes(attributes) => attributes.entries.get(index as usize).map(AsRef::as_ref),
+            _ => None,
+        })
+    }
+
+    fn label_section(&self) -> Option<&Labels> {
+        self.sections.iter().find_map(|section| match section {
+            Section::Labels(labels) => Some(labels),
+            _ => None,
+        })
+    }
+
+    fn index_of(&self, label: &str) -> Option<u32> {
+        self.labels().find(|(name, _)| *name == label).map(|(_, index)| index)
+    }
+}
+
+impl IntoBytes for Msbt {
+    fn into_bytes(self) -> Box<[u8]> {
+        let mut buf = self.header.to_vec();
+        buf[0xE..0x10].copy_from_slice(&(self.sections.len() as u16).to_le_bytes());
+
+        for section in self.sections {
+            let (magic, data) = match section {
+                Section::Labels(labels) => (*LBL1, labels.into_bytes()),
+                Section::Attributes(attributes) => (*ATR1, attributes.into_bytes()),
+                Section::Text(messages) => (*TXT2, text_into_bytes(messages)),
+                Section::Other(magic, data) => (magic, data.into_vec()),
+            };
+            buf.extend_from_slice(&magic);
+            buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&[0; 8]);
+            buf.extend_from_slice(&data);
+            buf.resize(align::<0x10>(buf.len() as u32) as usize, PADDING);
+        }
+
+        let size = buf.len() as u32;
+        buf[0x12..0x16].copy_from_slice(&size.to_le_bytes());
+        buf.into()
+    }
+}
+
+impl Labels {
+    fn parse(data: &[u8]) -> Result<Self> {
+        let groups = read_u32(data, 0)?;
+        let mut labels = vec![];
+        for group in 0..groups as usize {
+            let count = read_u32(data, 4 + group * 8)?;
+            let mut offset = read_u32(data, 8 + group * 8)? as usize;
+            for _ in 0..count {
+                let len = *data.get(offset).ok_or_else(|| Error::new("Ran out of data."))? as usize;
+                let name = data
+                    .get(offset + 1..offset + 1 + len)
+                    .ok_or_else(|| Error::new("Ran out of data."))?;
+                let name = String::from_utf8(name.into()).map_err(|_| Error::new("Label is not valid UTF-8."))?;
+                labels.push((name, read_u32(data, offset + 1 + len)?));
+                offset += 1 + len + 4;
+            }
+        }
+        labels.sort_by_key(|(_, index)| *index);
+        Ok(Self { groups, labels })
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let mut groups = vec![vec![]; self.groups as usize];
+        for (label, index) in &self.labels {
+            groups[label_hash(label, self.groups) as usize].push((label, *index));
+        }
+
+        let mut table = self.groups.to_le_bytes().to_vec();
+        let mut names = vec![];
+        let offset = 4 + 8 * self.groups;
+        for group in groups {
+            table.extend_from_slice(&(group.len() as u32).to_le_bytes());
+            table.extend_from_slice(&(offset + names.len() as u32).to_le_bytes());
+            for (label, index) in group {
+                names.push(label.len() as u8);
+                names.extend_from_slice(label.as_bytes());
+                names.extend_from_slice(&index.to_le_bytes());
+            }
+        }
+        table.append(&mut names);
+        table
+    }
+}
+
+impl Attributes {
+    fn parse(data: &[u8]) -> Result<Self> {
+        let count = read_u32(data, 0)? as usize;
+        let size = read_u32(data, 4)?;
+        let end = 8 + count * size as usize;
+        let entries = data
+            .get(8..end)
+            .ok_or_else(|| Error::new("Ran out of data."))?
+            .chunks(size.max(1) as usize)
+            .take(count)
+            .map(Into::into)
+            .collect();
+        Ok(Self {
+            size,
+            entries,
+            extra: data[end..].into(),
+        })
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let mut buf = vec![];
+        buf.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&self.size.to_le_bytes());
+        for entry in self.entries.iter() {
+            buf.extend_from_slice(entry);
+        }
+        buf.extend_from_slice(&self.extra);
+        buf
+    }
+}
+
+fn parse_text(data: &[u8]) -> Result<Vec<Message>> {
+    let count = read_u32(data, 0)? as usize;
+    let offsets = (0..count)
+        .map(|i| read_u32(data, 4 + i * 4).map(|offset| offset as usize))
+        .collect::<Result<Vec<_>>>()?;
+    offsets
+        .iter()
+        .enumerate()
+        .map(|(i, start)| {
+            let end = offsets.get(i + 1).copied().unwrap_or(data.len());
+            let units = data
+                .get(*start..end)
+                .ok_or_else(|| Error::new("Ran out of data."))?
+                .chunks_exact(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                .collect::<Vec<_>>();
+            Message::from_units(&units)
+        })
+        .collect()
+}
+
+fn text_into_bytes(messages: Vec<Message>) -> Vec<u8> {
+    let mut table = (messages.len() as u32).to_le_bytes().to_vec();
+    let mut strings = vec![];
+    let start = 4 + 4 * messages.len() as u32;
+    for message in messages {
+        table.extend_from_slice(&(start + strings.len() as u32).to_le_bytes());
+        for unit in message.into_units() {
+            strings.extend_from_slice(&unit.to_le_bytes());
+        }
+    }
+    table.append(&mut strings);
+    table
+}
+
+/// A single message, made up of plain text and control tags.
+///
+/// Messages convert to and from an escaped string, where tags are written as `{group.kind}` or `{group.kind:PARAMS}`
+/// with the parameter bytes in hex, closing tags as `{/group.kind}`, and a literal `{` as `{{`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Message(Vec<Token>);
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Token {
+    Text(String),
+    Tag { group: u16, kind: u16, params: Vec<u8> },
+    TagEnd { group: u16, kind: u16 },
+}
+
+impl Message {
+    pub fn tokens(&self) -> &[Token] {
+        &self.0
+    }
+
+    /// The message without any of its control tags.
+    pub fn text(&self) -> String {
+        self.0
+            .iter()
+            .filter_map(|token| match token {
+                Token::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Rewrites every run of plain text, leaving control tags untouched.
+    pub fn map_text<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&str) -> String,
+    {
+        for token in &mut self.0 {
+            if let Token::Text(text) = token {
+                *text = f(text);
+            }
+        }
+    }
+
+    fn from_units(units: &[u16]) -> Result<Self> {
+        let mut tokens = vec![];
+        let mut text = vec![];
+        let mut units = units.iter().copied();
+        while let Some(unit) = units.next() {
+            match unit {
+                0 => break,
+                TAG | TAG_END => {
+                    if !text.is_empty() {
+                        tokens.push(Token::Text(decode(&text)?));
+                        text.clear();
+                    }
+                    let mut next = || units.next().ok_or_else(|| Error::new("Unterminated control tag."));
+                    let group = next()?;
+                    let kind = next()?;
+                    if unit == TAG_END {
+                        tokens.push(Token::TagEnd { group, kind });
+                    } else {
+                        let size = next()? as usize;
+                        let params = (0..size.div_ceil(2))
+                            .map(|_| next())
+                            .collect::<Result<Vec<_>>>()?
+                            .into_iter()
+                            .flat_map(u16::to_le_bytes)
+                            .take(size)
+                            .collect();
+                        tokens.push(Token::Tag { group, kind, params });
+                    }
+                }
+                unit => text.push(unit),
+            }
+        }
+        if !text.is_empty() {
+            tokens.push(Token::Text(decode(&text)?));
+        }
+        Ok(Self(tokens))
+    }
+
+    fn into_units(self) -> Vec<u16> {
+        let mut units = vec![];
+        for token in self.0 {
+            match token {
+                Token::Text(text) => units.extend(text.encode_utf16()),
+                Token::Tag { group, kind, mut params } => {
+                    units.extend([TAG, group, kind, params.len() as u16]);
+                    if params.len() % 2 == 1 {
+                        params.push(0);
+                    }
+                    units.extend(params.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])));
+                }
+                Token::TagEnd { group, kind } => units.extend([TAG_END, group, kind]),
+            }
+        }
+        units.push(0);
+        units
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for token in &self.0 {
+            match token {
+                Token::Text(text) => f.write_str(&text.replace('{', "{{"))?,
+                Token::Tag { group, kind, params } => {
+                    write!(f, "{{{}.{}", group, kind)?;
+                    if !params.is_empty() {
+                        f.write_char(':')?;
+                        for byte in params {
+                            write!(f, "{:02X}", byte)?;
+                        }
+                    }
+                    f.write_char('}')?;
+                }
+                Token::TagEnd { group, kind } => write!(f, "{{/{}.{}}}", group, kind)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Message {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut tokens = vec![];
+        let mut text = String::new();
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            rest = &rest[start + 1..];
+            if let Some(after) = rest.strip_prefix('{') {
+                text.push('{');
+                rest = after;
+                continue;
+            }
+            let end = rest.find('}').ok_or_else(|| Error::new(format!("Unterminated tag in message: {}", s)))?;
+            if !text.is_empty() {
+                tokens.push(Token::Text(std::mem::take(&mut text)));
+            }
+            tokens.push(parse_tag(&rest[..end])?);
+            rest = &rest[end + 1..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            tokens.push(Token::Text(text));
+        }
+        Ok(Self(tokens))
+    }
+}
+
+impl From<&str> for Message {
+    /// Creates a message of plain text, without interpreting any escapes.
+    fn from(text: &str) -> Self {
+        Self(vec![Token::Text(text.to_string())])
+    }
+}
+
+fn parse_tag(tag: &str) -> Result<Token> {
+    let invalid = || Error::new(format!("Invalid tag: {{{}}}", tag));
+    let (closing, tag) = match tag.strip_prefix('/') {
+        Some(tag) => (true, tag),
+        None => (false, tag),
+    };
+    let (id, params) = tag.split_once(':').unwrap_or((tag, ""));
+    let (group, kind) = id.split_once('.').ok_or_else(invalid)?;
+    let group = group.parse().map_err(|_| invalid())?;
+    let kind = kind.parse().map_err(|_| invalid())?;
+    if closing {
+        return if params.is_empty() {
+            Ok(Token::TagEnd { group, kind })
+        } else {
+            Err(invalid())
+        };
+    }
+    // Checked up front, the pairs below are sliced by byte
+    if params.len() % 2 == 1 || !params.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let params = (0..params.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&params[i..i + 2], 16).map_err(|_| invalid()))
+        .collect::<Result<_>>()?;
+    Ok(Token::Tag { group, kind, params })
+}
+
+fn decode(units: &[u16]) -> Result<String> {
+    String::from_utf16(units).map_err(|_| Error::new("Message is not valid UTF-16."))
+}
+
+fn label_hash(label: &str, groups: u32) -> u32 {
+    label.bytes().fold(0u32, |hash, byte| hash.wrapping_mul(0x492).wrapping_add(byte as u32)) % groups
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| Error::new("Ran out of data."))
+}
+
+const MAGIC: &[u8; 8] = b"MsgStdBn";
+const LBL1: &[u8; 4] = b"LBL1";
+const ATR1: &[u8; 4] = b"ATR1";
+const TXT2: &[u8; 4] = b"TXT2";
+const UTF_16: u8 = 1;
+const TAG: u16 = 0xE;
+const TAG_END: u16 = 0xF;
+const PADDING: u8 = 0xAB;
+const HEADER_LEN: usize = 0x20;
+const SECTION_HEADER_LEN: usize = 0x10;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Msbt {
+        let mut header = [0; HEADER_LEN];
+        header[..8].copy_from_slice(MAGIC);
+        header[8..0xA].copy_from_slice(&[0xFF, 0xFE]);
+        header[0xC] = UTF_16;
+        header[0xD] = 3;
+        header[0x10] = 0x7F;
+        header[0x1F] = 0x42;
+        Msbt {
+            header,
+            sections: vec![
+                Section::Labels(Labels {
+                    groups: 101,
+                    labels: vec![("greeting".to_string(), 0), ("item_name".to_string(), 1)],
+                }),
+                Section::Attributes(Attributes {
+                    size: 4,
+                    entries: vec![[1, 0, 0, 0].into(), [2, 0, 0, 0].into()],
+                    extra: [].into(),
+                }),
+                Section::Text(vec![
+                    "Hello, {0.3:0100}Link{/0.3}! {{not a tag}".parse().unwrap(),
+                    Message::from("Bow"),
+                ]),
+            ],
+        }
+    }
+
+    #[test]
+    fn it_round_trips() {
+        let bytes = sample().into_bytes();
+        let msbt = Msbt::try_from_bytes(&bytes).unwrap();
+        assert_eq!(msbt.labels().collect::<Vec<_>>(), vec![("greeting", 0), ("item_name", 1)]);
+        assert_eq!(msbt.attribute("item_name"), Some(&[2, 0, 0, 0][..]));
+        assert_eq!(msbt.get("greeting").unwrap().to_string(), "Hello, {0.3:0100}Link{/0.3}! {{not a tag}");
+        assert_eq!(msbt.get("greeting").unwrap().text(), "Hello, Link! {not a tag}");
+        assert_eq!(&*msbt.into_bytes(), &*bytes);
+    }
+
+    #[test]
+    fn it_keeps_unknown_header_bytes() {
+        let bytes = sample().into_bytes();
+        assert_eq!(bytes[0x10], 0x7F);
+        assert_eq!(bytes[0x1F], 0x42);
+        assert_eq!(u16::from_le_bytes([bytes[0xE], bytes[0xF]]), 3);
+    }
+
+    #[test]
+    fn it_edits_messages() {
+        let mut msbt = sample();
+        *msbt.get_mut("item_name").unwrap() = Message::from("Nice Bow");
+        msbt.get_mut("greeting").unwrap().map_text(|text| text.replace("Link", "Zelda"));
+        let msbt = Msbt::try_from_bytes(&msbt.into_bytes()).unwrap();
+        assert_eq!(msbt.get("item_name").unwrap().text(), "Nice Bow");
+        assert_eq!(msbt.get("greeting").unwrap().to_string(), "Hello, {0.3:0100}Zelda{/0.3}! {{not a tag}");
+    }
+
+    #[test]
+    fn it_rejects_bad_tags() {
+        assert!("{0.3:0}".parse::<Message>().is_err());
+        assert!("{oops}".parse::<Message>().is_err());
+        assert!("{0.3".parse::<Message>().is_err());
+        assert!("{0.3:aé1}".parse::<Message>().is_err());
+        assert!("{0.3:+1}".parse::<Message>().is_err());
+    }
+}
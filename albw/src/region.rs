@@ -0,0 +1,65 @@
+//! The release of the game that can be loaded, and where it keeps its region-specific files.
+//!
+//! Only the North American release is supported. The European and Japanese releases aren't listed, since their
+//! folders and languages haven't been checked against real dumps.
+
+use crate::course;
+
+/// A release of the game.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Region {
+    /// A readable name for the release.
+    pub name: &'static str,
+    /// The 64-bit title ID.
+    pub id: u64,
+    /// The RomFS folder holding `RegionBoot.szs`, and the prefix of each language's folder.
+    prefix: &'static str,
+    /// The languages the release has a text archive for each course in.
+    languages: &'static [&'static str],
+}
+
+impl Region {
+    /// Finds the release with the given title ID.
+    pub fn from_id(id: u64) -> Option<&'static Self> {
+        REGIONS.iter().find(|region| region.id == id)
+    }
+
+    /// Iterates over every supported release, which is only the North American one.
+    pub fn iter() -> impl Iterator<Item = &'static Self> {
+        REGIONS.iter()
+    }
+
+    pub(crate) fn boot_path(&self) -> String {
+        format!("{}/RegionBoot.szs", self.prefix)
+    }
+
+    /// Gets the languages the release ships with, such as `English`.
+    pub fn languages(&self) -> &'static [&'static str] {
+        self.languages
+    }
+
+    pub(crate) fn language_path(&self, course: course::Id, language: &str) -> String {
+        format!("{}_{}/{}.szs", self.prefix, language, course.as_str())
+    }
+}
+
+/// Every supported release, only North America so far.
+static REGIONS: &[Region] = &[Region {
+    name: "North America",
+    id: 0x00040000000EC300,
+    prefix: "US",
+    languages: &["English", "French", "Spanish"],
+}];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_finds_regions() {
+        let region = Region::from_id(0x00040000000EC300).unwrap();
+        assert_eq!(region.boot_path(), "US/RegionBoot.szs");
+        assert_eq!(region.language_path(course::Id::FieldLight, "French"), "US_French/FieldLight.szs");
+        assert!(Region::from_id(0).is_none());
+    }
+}
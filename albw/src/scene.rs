@@ -57,6 +57,14 @@ pub struct Stage {
 }
 
 impl Stage {
+    pub fn objs(&self) -> &[Obj] {
+        &self.objs
+    }
+
+    pub fn system(&self) -> &[Obj] {
+        &self.system
+    }
+
     pub fn get_mut(&mut self, unq: u16) -> Option<&mut Obj> {
         if let Some(i) = self.objs.iter().position(|obj| obj.unq == unq) {
             self.objs.get_mut(i)
@@ -93,10 +101,18 @@ pub struct Obj {
 }
 
 impl Obj {
+    pub fn arg(&self) -> &Arg {
+        &self.arg
+    }
+
     pub fn arg_mut(&mut self) -> &mut Arg {
         &mut self.arg
     }
 
+    pub fn lnk(&self) -> &[Lnk] {
+        &self.lnk
+    }
+
     pub fn set_clp(&mut self, clp: i16) {
         self.clp = clp;
     }
@@ -189,6 +205,14 @@ impl Obj {
         self.arg.7 = 0;
     }
 
+    pub fn id(&self) -> i16 {
+        self.id
+    }
+
+    pub fn unq(&self) -> u16 {
+        self.unq
+    }
+
     pub fn set_id(&mut self, id: i16) {
         self.id = id;
     }
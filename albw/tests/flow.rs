@@ -16,7 +16,9 @@ fn it_works() -> Result<()> {
     let game = common::load()?;
     iterate(game.boot()?)?;
     for course in course::Id::iter() {
-        iterate(game.course(course).language()?)?;
+        for language in game.region().languages() {
+            iterate(game.course(course).language(language)?)?;
+        }
     }
     Ok(())
 }
@@ -1,28 +1,289 @@
-use std::{fs, panic};
-use std::io::{stdin, stdout, Read, Write};
-use std::path::Path;
+use std::{fmt, fs, panic};
+use std::fmt::{Display, Formatter};
+use std::io::{stdin, stdout, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
 use log::{error, info};
 
-use randomizer::{Seed, Settings, plando, filler_new};
-use simplelog::{LevelFilter, SimpleLogger};
+use randomizer::{ErrorKind, Seed, SeedFile, Settings, plando, filler_new, CodeFormat, OutputFormat};
+use serde_json::{json, Value};
+use simplelog::{LevelFilter, SimpleLogger, WriteLogger};
+use structopt::clap;
 use structopt::StructOpt;
 use albw::Game;
 use randomizer::logic_mode::LogicMode;
-use randomizer::settings::Logic;
-use sys::Paths;
+use randomizer::settings::{Logic, Options};
+use sys::{Paths, System};
 
+mod tui;
+
+/// Runs `generate` when no subcommand is given, see [`parse_args`].
 #[derive(Debug, StructOpt)]
 struct Opt {
+    /// Never prompt or wait for input; fail if anything needed is missing instead.
+    #[structopt(long, global = true)]
+    non_interactive: bool,
+    /// Print the result as JSON on stdout and log to stderr. Implies --non-interactive.
+    #[structopt(long, global = true)]
+    json: bool,
+    /// Directory holding config.toml and the presets folder, instead of $Z17R_CONFIG_DIR, the working directory or
+    /// the platform's config directory.
+    #[structopt(long, global = true, parse(from_os_str))]
+    config_dir: Option<PathBuf>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Generates a seed and patches the ROM with it.
+    Generate(Generate),
+    /// Rebuilds a shared seed's patch from a seed file or spoiler log and your own ROM.
+    #[structopt(alias = "apply")]
+    Patch {
+        /// A seed file, or a spoiler log if it ends in .json.
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        #[structopt(flatten)]
+        output: Output,
+    },
+    /// Checks that the ROM can be used by the randomizer.
+    Verify {
+        /// Path to the ROM, instead of the one in config.toml.
+        #[structopt(long, parse(from_os_str))]
+        rom: Option<PathBuf>,
+    },
+    /// Lists every check items can be placed at, by the names used in exclusions and placement rules.
+    ListChecks,
+    /// Works with the presets in the presets folder.
+    Presets(Presets),
+    /// Prints the JSON Schema of the preset format.
+    Schema,
+}
+
+#[derive(Debug, StructOpt)]
+enum Presets {
+    /// Lists the names of the available presets.
+    List,
+    /// Prints a preset's effective settings, with the presets it extends merged in and defaults filled in.
+    Show {
+        name: String,
+    },
+    /// Opens a preset in the settings editor, or starts a new one.
+    Edit {
+        name: Option<String>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+struct Generate {
     #[structopt(long)]
     seed: Option<Seed>,
-    #[structopt(long)]
+    /// Name of a preset in the presets folder to start from.
+    #[structopt(long, conflicts_with = "settings")]
     preset: Option<String>,
+    /// Path to a settings file, in the same format as a preset, to start from.
+    #[structopt(long, parse(from_os_str))]
+    settings: Option<PathBuf>,
+    #[structopt(flatten)]
+    logic: LogicFlags,
+    #[structopt(flatten)]
+    options: OptionFlags,
     #[structopt(long)]
     no_patch: bool,
+    #[structopt(flatten)]
+    output: Output,
+}
+
+/// Where to find the ROM and how to write the patched game.
+#[derive(Debug, StructOpt)]
+struct Output {
+    /// Path to the ROM, instead of the one in config.toml.
+    #[structopt(long, parse(from_os_str))]
+    rom: Option<PathBuf>,
+    /// Output directory, instead of the one in config.toml.
+    #[structopt(long, parse(from_os_str))]
+    output: Option<PathBuf>,
     #[structopt(long)]
     no_spoiler: bool,
+    /// How to write the patched game: layeredfs, luma, citra, romfs, 3ds or cxi.
+    #[structopt(long, default_value = "layeredfs")]
+    format: OutputFormat,
+    /// How to write the code patch in a mod folder: ips, bps or bin.
+    #[structopt(long, default_value = "ips")]
+    code: CodeFormat,
 }
 
+/// Declares flags that each override one field of a settings section, if given.
+macro_rules! overrides {
+    ($(#[$attr:meta])* struct $name:ident for $section:ty {
+        $($(#[doc = $doc:literal])* $field:ident: $type:ty,)+
+    }) => {
+        $(#[$attr])*
+        #[derive(Debug, StructOpt)]
+        struct $name {
+            $(
+                $(#[doc = $doc])*
+                #[structopt(long)]
+                $field: Option<$type>,
+            )+
+        }
+
+        impl $name {
+            fn is_empty(&self) -> bool {
+                $(self.$field.is_none())&&+
+            }
+
+            fn apply(&self, section: &mut $section) {
+                $(
+                    if let Some(value) = &self.$field {
+                        section.$field = value.clone();
+                    }
+                )+
+            }
+        }
+    };
+}
+
+overrides! {
+    /// Overrides for the `[logic]` settings.
+    struct LogicFlags for Logic {
+        /// normal, hard, glitch-basic, glitch-advanced, glitch-hell or no-logic
+        mode: LogicMode,
+        /// Guarantee a weapon in Ravio's Shop [true/false]
+        assured_weapon: bool,
+        /// Place the Bell in Ravio's Shop [true/false]
+        bell_in_shop: bool,
+        /// Place the Pouch in Ravio's Shop [true/false]
+        pouch_in_shop: bool,
+        /// Place the Pegasus Boots in Ravio's Shop [true/false]
+        boots_in_shop: bool,
+        /// Keep progression out of minigames [true/false]
+        minigames_excluded: bool,
+        /// Play without a sword [true/false]
+        swordless_mode: bool,
+        /// Shuffle the Super Lamp and Super Net [true/false]
+        super_items: bool,
+        /// Shuffle Nice upgrades of Ravio's items [true/false]
+        nice_items: bool,
+        /// Skip the Lorule Castle Trials [true/false]
+        skip_trials: bool,
+        /// Place the Bow of Light in Lorule Castle [true/false]
+        bow_of_light_in_castle: bool,
+        /// Allow dark rooms to be required without the Lamp [true/false]
+        lampless: bool,
+    }
+}
+
+overrides! {
+    /// Overrides for the `[options]` settings.
+    struct OptionFlags for Options {
+        /// Use Hyrule's nighttime colors until visiting Lorule [true/false]
+        night_mode: bool,
+        /// Shuffle enemies across every scene [true/false]
+        enemy_shuffle: bool,
+        /// Only write these languages, leaving the others vanilla
+        languages: Vec<String>,
+    }
+}
+
+/// Why a command failed, which decides the exit code.
+#[derive(Debug)]
+enum Failure {
+    /// The arguments or config can't be used as given.
+    Usage(String),
+    /// Every attempt to generate a seed produced one that couldn't be completed.
+    Uncompletable(u32),
+    /// The user left the settings editor without using the settings.
+    Cancelled,
+    Randomizer(randomizer::Error),
+}
+
+impl Failure {
+    /// The exit code, as listed in the README.
+    fn code(&self) -> i32 {
+        match self {
+            Self::Usage(_) => 2,
+            Self::Uncompletable(_) => 6,
+            Self::Cancelled => 1,
+            Self::Randomizer(err) => match err.kind() {
+                ErrorKind::Sys | ErrorKind::InvalidSettings => 2,
+                ErrorKind::RomNotFound => 3,
+                ErrorKind::InvalidRom(_) => 4,
+                ErrorKind::OutputNotWritable => 5,
+                ErrorKind::Game | ErrorKind::Io => 1,
+            },
+        }
+    }
+
+    /// A stable name for the kind of failure, for JSON output.
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Usage(_) => "usage",
+            Self::Uncompletable(_) => "uncompletable",
+            Self::Cancelled => "cancelled",
+            Self::Randomizer(err) => match err.kind() {
+                ErrorKind::Sys => "config",
+                ErrorKind::InvalidSettings => "invalid_settings",
+                ErrorKind::Game => "game",
+                ErrorKind::Io => "io",
+                ErrorKind::RomNotFound => "rom_not_found",
+                ErrorKind::InvalidRom(_) => "invalid_rom",
+                ErrorKind::OutputNotWritable => "output_not_writable",
+            },
+        }
+    }
+
+    /// What the user can change to fix this, if anything.
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::Randomizer(err) => match err.kind() {
+                ErrorKind::RomNotFound | ErrorKind::InvalidRom(_) => {
+                    Some("Please check that config.toml points to a valid ROM.")
+                }
+                ErrorKind::OutputNotWritable => {
+                    Some("Please check that config.toml points to a valid output destination.")
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(message) => f.write_str(message),
+            Self::Uncompletable(attempts) => {
+                write!(f, "No completable seed was generated in {} attempts.", attempts)
+            }
+            Self::Cancelled => f.write_str("Cancelled."),
+            Self::Randomizer(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<randomizer::Error> for Failure {
+    fn from(err: randomizer::Error) -> Self {
+        Self::Randomizer(err)
+    }
+}
+
+impl From<sys::Error> for Failure {
+    fn from(err: sys::Error) -> Self {
+        Self::Randomizer(err.into())
+    }
+}
+
+impl From<albw::Error> for Failure {
+    fn from(err: albw::Error) -> Self {
+        Self::Randomizer(err.into())
+    }
+}
+
+type Result<T, E = Failure> = std::result::Result<T, E>;
+
 fn prompt_logic_mode() -> LogicMode
 {
     print!("\nChoose Logic Mode:\n");
@@ -96,7 +357,13 @@ fn prompt_until<F>(prompt: &str, until: F, error: &str) -> sys::Result<String>
 fn create_paths() -> sys::Result<Paths> {
     let rom = prompt_until(
         "Path to ROM",
-        |rom| Game::load(&rom).is_ok(),
+        |rom| match Game::load(rom) {
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!("{}", err);
+                false
+            }
+        },
         "The provided path does not point to a valid ROM.",
     )?;
     let output = prompt_until(
@@ -122,6 +389,7 @@ fn preset_ui() -> Settings {
     let boots_in_shop = prompt_until_bool("Guarantee Pegasus Boots in Ravio's Shop?");
 
     let super_items = prompt_until_bool("Include the Super Lamp and Super Net?");
+    let nice_items = prompt_until_bool("Shuffle a second copy of each of Ravio's items as Nice upgrades?");
     let minigames_excluded = prompt_until_bool("Exclude all minigames?");
     let skip_trials = prompt_until_bool("Skip the Lorule Castle Trials?");
     let bow_of_light_in_castle = prompt_until_bool("Guarantee Bow of Light in Lorule Castle?");
@@ -140,6 +408,7 @@ fn preset_ui() -> Settings {
             pouch_in_shop,
             boots_in_shop,
             super_items,
+            nice_items,
             //glitched_logic,
             minigames_excluded,
             skip_trials,
@@ -159,65 +428,286 @@ fn pause() {
     stdin().read(&mut [0]).unwrap();
 }
 
-fn main() -> randomizer::Result<()> {
-    let opt = Opt::from_args();
+/// Gets the paths from config.toml, with any given on the command line taking precedence.
+///
+/// Without a config, both paths must be given unless the user can be prompted for them.
+fn paths(
+    system: &System<Settings>,
+    rom: Option<&PathBuf>,
+    output: Option<&PathBuf>,
+    interactive: bool,
+) -> Result<Paths> {
+    let mut paths = match (system.paths()?, rom, output) {
+        (Some(paths), _, _) => paths,
+        (None, Some(rom), Some(output)) => Paths::new(rom.clone(), output.clone()),
+        (None, _, _) if interactive => system.get_or_create_paths(create_paths)?,
+        (None, _, _) => {
+            return Err(Failure::Usage(
+                "No config.toml was found, so both --rom and --output must be given.".to_string(),
+            ));
+        }
+    };
+    if let Some(rom) = rom {
+        paths.set_rom(rom.clone());
+    }
+    if let Some(output) = output {
+        paths.set_output(output.clone());
+    }
+    Ok(paths)
+}
 
-    SimpleLogger::init(LevelFilter::Info, Default::default()).expect("Could not initialize logger.");
+/// Builds the settings from the preset or settings file, with any flags applied on top.
+///
+/// If none of those were given, the user is asked for the settings when they can be, in the settings editor if
+/// this is a terminal.
+fn settings(system: &System<Settings>, opt: &Generate, interactive: bool) -> Result<Settings> {
+    let mut settings = if let Some(ref preset) = opt.preset {
+        system.preset(preset)?
+    } else if let Some(ref path) = opt.settings {
+        system.preset_file(path)?
+    } else if interactive && opt.logic.is_empty() && opt.options.is_empty() {
+        if stdout().is_terminal() {
+            tui::edit(system, Settings::default(), None, Vec::new(), true)
+                .map_err(randomizer::Error::from)?
+                .0
+                .ok_or(Failure::Cancelled)?
+        } else {
+            preset_ui()
+        }
+    } else {
+        Settings::default()
+    };
+    opt.logic.apply(&mut settings.logic);
+    opt.options.apply(&mut settings.options);
+    settings.validate().map_err(randomizer::Error::from)?;
+    Ok(settings)
+}
 
-    info!("Initializing Z17 Randomizer...");
+fn generate(system: &System<Settings>, opt: &Generate, interactive: bool) -> Result<Value> {
+    let settings = settings(system, opt, interactive)?;
+    let paths = paths(system, opt.output.rom.as_ref(), opt.output.output.as_ref(), interactive)?;
+    let output = paths.output().to_path_buf();
+
+    let max_retries = 100;
+
+    for x in 0..max_retries {
+        let seed = opt.seed.unwrap_or_else(rand::random);
+
+        info!("Attempt:                        #{}", x + 1);
+        info!("Preset:                         {}", opt.preset.as_ref().unwrap_or(&String::from("<None>")));
+        info!("Version:                        {}", env!("CARGO_PKG_VERSION"));
+
+        //let randomizer = Generator::new(&preset, seed);
+        let spoiler = panic::catch_unwind(|| filler_new(&settings, seed));
+
+        if let Ok(spoiler) = spoiler {
+            info!("Seed generated. Patching...");
+            let hash = spoiler.seed_file().hash().to_string();
+            spoiler.patch(
+                paths,
+                !opt.no_patch,
+                !opt.output.no_spoiler,
+                opt.output.format.with_code(opt.output.code),
+            )?;
+            info!("Successfully generated Z17R seed :D");
+            return Ok(json!({ "seed": seed, "hash": hash, "output": output }));
+        } else {
+            info!("Seed was not completable (this is normal). Retrying...\n");
+        }
+    }
 
-    let is_plando = false; // TODO expose this eventually so people can make their own, for now it's for testing
+    Err(Failure::Uncompletable(max_retries))
+}
 
-    if is_plando {
-        plando()
+fn patch(system: &System<Settings>, file: &Path, opt: &Output, interactive: bool) -> Result<Value> {
+    let seed_file = if file.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json")) {
+        info!("Reading spoiler log from:       {}", file.display());
+        SeedFile::read_spoiler(file)?
     } else {
-        let system = randomizer::system()?;
+        info!("Reading seed file from:         {}", file.display());
+        SeedFile::read(file)?
+    };
+    info!("Seed hash:                      {}", seed_file.hash());
+    let paths = paths(system, opt.rom.as_ref(), opt.output.as_ref(), interactive)?;
+    let output = paths.output().to_path_buf();
+    seed_file.spoiler()?.patch(
+        paths,
+        true,
+        !opt.no_spoiler,
+        opt.format.with_code(opt.code),
+    )?;
+    info!("Successfully applied Z17R seed :D");
+    Ok(json!({ "seed": seed_file.seed(), "hash": seed_file.hash(), "output": output }))
+}
 
-        let preset = if let Some(ref preset) = opt.preset {
-            system.preset(&preset)?
-        } else {
-            preset_ui()
-        };
+fn verify(system: &System<Settings>, rom: Option<&PathBuf>, interactive: bool) -> Result<Value> {
+    // Same rule as generating: check against the known dumps unless config.toml turns it off
+    let (rom, verify_rom) = match (rom, system.paths()?) {
+        (Some(rom), paths) => (rom.clone(), paths.is_none_or(|paths| paths.verify_rom())),
+        (None, _) => {
+            let paths = paths(system, None, None, interactive)?;
+            (paths.rom().to_path_buf(), paths.verify_rom())
+        }
+    };
+    let game = if verify_rom { Game::load(&rom)? } else { Game::load_unverified(&rom)? };
+    let region = game.region();
+    info!("ROM is a valid {} release ({:016X}).", region.name, region.id);
+    Ok(json!({ "rom": rom, "region": region.name, "id": format!("{:016X}", region.id) }))
+}
+
+fn list_checks(json: bool) -> Result<Value> {
+    let checks = randomizer::checks();
+    if !json {
+        for (name, location) in &checks {
+            println!("{:<10}{:<32}{}", format!("{:?}", location.world()), location.region(), name);
+        }
+    }
+    let checks = checks
+        .iter()
+        .map(|(name, location)| {
+            json!({ "name": name, "region": location.region(), "world": format!("{:?}", location.world()) })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({ "checks": checks }))
+}
+
+fn list_presets(system: &System<Settings>, json: bool) -> Result<Value> {
+    let presets = system.presets()?;
+    info!("Presets folder:                 {}", system.presets_dir().display());
+    if !json {
+        for preset in &presets {
+            println!("{}", preset);
+        }
+    }
+    Ok(json!({ "presets": presets, "folder": system.presets_dir() }))
+}
+
+fn show_preset(system: &System<Settings>, name: &str, json: bool) -> Result<Value> {
+    let settings = system.preset(name)?;
+    if !json {
+        // Going through a value puts plain values ahead of tables, as TOML requires
+        let value = toml::Value::try_from(&settings).expect("Could not serialize the preset.");
+        print!("{}", toml::to_string_pretty(&value).expect("Could not serialize the preset."));
+    }
+    Ok(json!({ "name": name, "settings": settings }))
+}
+
+fn edit_preset(system: &System<Settings>, name: Option<&String>, interactive: bool) -> Result<Value> {
+    if !interactive || !stdout().is_terminal() {
+        return Err(Failure::Usage("The settings editor needs an interactive terminal.".to_string()));
+    }
+    let (settings, extends) = match name {
+        Some(name) if system.presets()?.contains(name) => (system.preset(name)?, system.extends(name)?),
+        _ => (Settings::default(), Vec::new()),
+    };
+    let (_, name) = tui::edit(system, settings, name.cloned(), extends, false).map_err(randomizer::Error::from)?;
+    Ok(json!({ "name": name }))
+}
 
-        let max_retries = 100;
-        let mut result = Ok(());
+fn schema(json: bool) -> Result<Value> {
+    let schema = serde_json::to_value(Settings::schema()).expect("Could not serialize the schema.");
+    if !json {
+        println!("{}", serde_json::to_string_pretty(&schema).expect("Could not serialize the schema."));
+    }
+    Ok(json!({ "schema": schema }))
+}
 
-        for x in 0..max_retries {
-            let seed = opt.seed.unwrap_or_else(rand::random);
+fn run(opt: &Opt, interactive: bool) -> Result<Value> {
+    let system = || randomizer::system(opt.config_dir.clone());
+    match &opt.command {
+        None => unreachable!("a subcommand is always parsed"),
+        Some(Command::Generate(generate_opt)) => generate(&system()?, generate_opt, interactive),
+        Some(Command::Patch { file, output }) => patch(&system()?, file, output, interactive),
+        Some(Command::Verify { rom }) => verify(&system()?, rom.as_ref(), interactive),
+        Some(Command::ListChecks) => list_checks(opt.json),
+        Some(Command::Presets(Presets::List)) => list_presets(&system()?, opt.json),
+        Some(Command::Presets(Presets::Show { name })) => show_preset(&system()?, name, opt.json),
+        Some(Command::Presets(Presets::Edit { name })) => edit_preset(&system()?, name.as_ref(), interactive),
+        Some(Command::Schema) => schema(opt.json),
+    }
+}
+
+/// Parses the arguments as `generate`'s when no subcommand is given, so its flags are only defined once.
+fn parse_args() -> clap::Result<Opt> {
+    let args = std::env::args_os().collect::<Vec<_>>();
+    let with_generate = || {
+        let mut args = args.clone();
+        args.insert(1, "generate".into());
+        Opt::from_iter_safe(args)
+    };
+    match Opt::from_iter_safe(&args) {
+        Ok(Opt { command: None, .. }) => with_generate(),
+        // Report the flag as misplaced rather than unknown if it isn't one of `generate`'s either
+        Err(err) if err.kind == clap::ErrorKind::UnknownArgument => {
+            with_generate().map_err(|retry| if retry.kind == clap::ErrorKind::UnknownArgument { err } else { retry })
+        }
+        result => result,
+    }
+}
 
+fn main() {
+    let opt = match parse_args() {
+        Ok(opt) => opt,
+        Err(err) if matches!(err.kind, clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed) => {
+            err.exit()
+        }
+        Err(err) => {
+            eprintln!("{}", err.message);
+            exit(2);
+        }
+    };
+    let interactive = !(opt.non_interactive || opt.json);
 
-            info!("Attempt:                        #{}", x + 1);
-            info!("Preset:                         {}", opt.preset.as_ref().unwrap_or(&String::from("<None>")));
-            info!("Version:                        0.1.2");
+    // Keep stdout to the data itself when it's meant to be read by something else
+    if opt.json || matches!(opt.command, Some(Command::ListChecks | Command::Presets(_) | Command::Schema)) {
+        WriteLogger::init(LevelFilter::Info, Default::default(), std::io::stderr())
+    } else {
+        SimpleLogger::init(LevelFilter::Info, Default::default())
+    }
+    .expect("Could not initialize logger.");
 
-            //let randomizer = Generator::new(&preset, seed);
-            let spoiler = panic::catch_unwind(|| filler_new(&preset, seed));
+    info!("Initializing Z17 Randomizer...");
 
-            if spoiler.is_ok() {
-                println!();
-                info!("Seed generated. Patching...");
-                result = spoiler.unwrap().patch(
-                    system.get_or_create_paths(create_paths)?,
-                    !opt.no_patch,
-                    !opt.no_spoiler,
-                );
+    let is_plando = false; // TODO expose this eventually so people can make their own, for now it's for testing
 
-                break;
-            } else if x >= max_retries - 1 {
-                // FIXME I hate this, but I'm struggling with Rust error handling so leaving it for now
-                panic!("Too many retry attempts have failed. Aborting...");
-            } else {
-                info!("Seed was not completable (this is normal). Retrying...\n");
+    let result = if is_plando {
+        randomizer::system(opt.config_dir.clone())
+            .map_err(Failure::from)
+            .and_then(|system| paths(&system, None, None, interactive))
+            .and_then(|paths| plando(paths).map_err(Failure::from))
+            .map(|_| Value::Null)
+    } else {
+        run(&opt, interactive)
+    };
+
+    let code = match result {
+        Ok(mut report) => {
+            if opt.json {
+                report["status"] = json!("ok");
+                println!("{}", report);
             }
+            0
         }
-
-        match result {
-            Ok(_) => info!("Successfully generated Z17R seed :D"),
-            Err(_) => error!("Failed to generate Z17R seed D:"),
+        Err(failure) => {
+            if opt.json {
+                println!("{}", json!({ "status": "error", "kind": failure.kind(), "message": failure.to_string() }));
+            } else {
+                for line in failure.to_string().lines() {
+                    error!("{}", line);
+                }
+                if let Some(hint) = failure.hint() {
+                    error!("{}", hint);
+                }
+                error!("Failed D:");
+            }
+            failure.code()
         }
+    };
 
+    // Only the commands people run by opening the executable wait, so their window stays open to read the result
+    if interactive && matches!(opt.command, None | Some(Command::Generate(_)) | Some(Command::Patch { .. })) {
         pause();
-
-        result
     }
+
+    exit(code);
 }
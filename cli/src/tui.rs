@@ -0,0 +1,434 @@
+//! A terminal editor for settings, which can save them as a preset or generate a seed with them.
+
+use std::io;
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Tabs, Wrap},
+    DefaultTerminal, Frame,
+};
+use randomizer::{settings::Settings, LocationInfo};
+use serde_json::Value;
+use sys::System;
+
+/// The sections of the settings with fields that can be edited, by their key in a preset.
+const SECTIONS: [&str; 2] = ["logic", "options"];
+
+/// One setting that can be edited, found from the settings schema so new settings show up here without changes.
+struct Field {
+    section: &'static str,
+    name: String,
+    description: String,
+    kind: Kind,
+}
+
+enum Kind {
+    Toggle,
+    /// One of a fixed set of values, such as the logic mode.
+    Choice(Vec<String>),
+}
+
+/// Finds every field of the editable sections that's a toggle or a choice.
+///
+/// Fields that are lists, such as the languages to patch, are left to preset files.
+fn fields() -> Vec<Field> {
+    let schema = serde_json::to_value(Settings::schema()).expect("Could not serialize the schema.");
+    let definition = |reference: &Value| {
+        reference
+            .as_str()
+            .and_then(|reference| reference.strip_prefix("#/definitions/"))
+            .map(|name| &schema["definitions"][name])
+    };
+
+    let mut fields = Vec::new();
+    for section in SECTIONS {
+        let properties = definition(&schema["properties"][section]["allOf"][0]["$ref"])
+            .and_then(|section| section["properties"].as_object());
+        for (name, property) in properties.into_iter().flatten() {
+            let kind = if property["type"] == "boolean" {
+                Kind::Toggle
+            } else if let Some(values) =
+                definition(&property["allOf"][0]["$ref"]).and_then(|choice| choice["enum"].as_array())
+            {
+                Kind::Choice(values.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            } else {
+                continue;
+            };
+            fields.push(Field {
+                section,
+                name: name.clone(),
+                description: property["description"].as_str().unwrap_or_default().to_string(),
+                kind,
+            });
+        }
+    }
+    fields
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Tab {
+    Logic,
+    Options,
+    Exclusions,
+}
+
+impl Tab {
+    const ALL: [Self; 3] = [Self::Logic, Self::Options, Self::Exclusions];
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::Logic => "Logic",
+            Self::Options => "Options",
+            Self::Exclusions => "Exclusions",
+        }
+    }
+}
+
+/// Text being typed in at the bottom of the screen.
+enum Input {
+    Search,
+    SaveAs(String),
+}
+
+struct Editor<'s> {
+    system: &'s System<Settings>,
+    settings: Settings,
+    /// The preset to save to, which is asked for on the first save if there isn't one.
+    name: Option<String>,
+    /// The presets the edited one extends, whose settings are left out when saving.
+    extends: Vec<String>,
+    /// Whether leaving with Enter generates a seed, rather than the editor only being used to save presets.
+    generate: bool,
+    fields: Vec<Field>,
+    checks: Vec<(&'static str, LocationInfo)>,
+    tab: Tab,
+    list: ListState,
+    search: String,
+    input: Option<Input>,
+    status: String,
+}
+
+impl<'s> Editor<'s> {
+    fn fields_in(&self, tab: Tab) -> Vec<&Field> {
+        let section = match tab {
+            Tab::Logic => "logic",
+            Tab::Options => "options",
+            Tab::Exclusions => return Vec::new(),
+        };
+        self.fields.iter().filter(|field| field.section == section).collect()
+    }
+
+    /// The checks matching the search, by name, region or world.
+    fn matching_checks(&self) -> Vec<&(&'static str, LocationInfo)> {
+        let search = self.search.to_lowercase();
+        self.checks
+            .iter()
+            .filter(|(name, location)| {
+                name.to_lowercase().contains(&search)
+                    || location.region().to_lowercase().contains(&search)
+                    || format!("{:?}", location.world()).to_lowercase().contains(&search)
+            })
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        match self.tab {
+            Tab::Exclusions => self.matching_checks().len(),
+            tab => self.fields_in(tab).len(),
+        }
+    }
+
+    fn value(&self, field: &Field) -> Value {
+        let settings = serde_json::to_value(&self.settings).expect("Could not serialize the settings.");
+        settings[field.section][&field.name].clone()
+    }
+
+    /// Changes the selected field, stepping through the choices in the given direction.
+    fn change(&mut self, forward: bool) {
+        let Some(field) = self.list.selected().and_then(|selected| self.fields_in(self.tab).get(selected).copied()) else {
+            return;
+        };
+        let value = match (&field.kind, self.value(field)) {
+            (Kind::Toggle, Value::Bool(enabled)) => Value::Bool(!enabled),
+            (Kind::Choice(choices), Value::String(current)) => {
+                let index = choices.iter().position(|choice| *choice == current).unwrap_or_default();
+                let index = if forward {
+                    (index + 1) % choices.len()
+                } else {
+                    (index + choices.len() - 1) % choices.len()
+                };
+                Value::String(choices[index].clone())
+            }
+            _ => return,
+        };
+        let (section, name) = (field.section, field.name.clone());
+        let mut settings = serde_json::to_value(&self.settings).expect("Could not serialize the settings.");
+        settings[section][name] = value;
+        match serde_json::from_value(settings) {
+            Ok(settings) => self.settings = settings,
+            Err(err) => self.status = err.to_string(),
+        }
+    }
+
+    /// Moves the selected check between included, excluded and prioritized.
+    fn toggle_check(&mut self, priority: bool) {
+        let Some(name) = self
+            .list
+            .selected()
+            .and_then(|selected| self.matching_checks().get(selected).map(|(name, _)| name.to_string()))
+        else {
+            return;
+        };
+        let exclusions = &mut self.settings.exclusions;
+        let (set, other) = if priority {
+            (&mut exclusions.priority, &mut exclusions.exclusions)
+        } else {
+            (&mut exclusions.exclusions, &mut exclusions.priority)
+        };
+        if !set.remove(&name) {
+            other.remove(&name);
+            set.insert(name);
+        }
+    }
+
+    fn save(&mut self, name: String) {
+        self.status = match self.system.save_preset(&name, &self.settings, &self.extends) {
+            Ok(path) => {
+                self.name = Some(name);
+                match self.settings.validate() {
+                    Ok(()) => format!("Saved to {}", path.display()),
+                    Err(err) => format!("Saved to {}, but it can't be used yet: {}", path.display(), first(&err.to_string())),
+                }
+            }
+            Err(err) => err.to_string(),
+        };
+    }
+
+    fn switch(&mut self, tab: Tab) {
+        self.tab = tab;
+        self.list.select(Some(0));
+    }
+
+    /// Handles a key press, returning whether the editor is done and whether the settings should be used.
+    fn key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Option<bool> {
+        match (&mut self.input, code) {
+            (Some(Input::Search), KeyCode::Enter | KeyCode::Esc) => self.input = None,
+            (Some(Input::Search), KeyCode::Backspace) => {
+                self.search.pop();
+                self.list.select(Some(0));
+            }
+            (Some(Input::Search), KeyCode::Char(c)) => {
+                self.search.push(c);
+                self.list.select(Some(0));
+            }
+            (Some(Input::SaveAs(_)), KeyCode::Esc) => self.input = None,
+            (Some(Input::SaveAs(name)), KeyCode::Backspace) => {
+                name.pop();
+            }
+            (Some(Input::SaveAs(name)), KeyCode::Char(c)) => name.push(c),
+            (Some(Input::SaveAs(name)), KeyCode::Enter) => {
+                let name = name.trim().to_string();
+                self.input = None;
+                self.save(name);
+            }
+            (Some(_), _) => {}
+            (None, KeyCode::Char('c')) if modifiers.contains(KeyModifiers::CONTROL) => return Some(false),
+            (None, KeyCode::Char('q') | KeyCode::Esc) => return Some(false),
+            (None, KeyCode::Tab) => {
+                let index = Tab::ALL.iter().position(|tab| *tab == self.tab).unwrap_or_default();
+                self.switch(Tab::ALL[(index + 1) % Tab::ALL.len()]);
+            }
+            (None, KeyCode::BackTab) => {
+                let index = Tab::ALL.iter().position(|tab| *tab == self.tab).unwrap_or_default();
+                self.switch(Tab::ALL[(index + Tab::ALL.len() - 1) % Tab::ALL.len()]);
+            }
+            (None, KeyCode::Up | KeyCode::Char('k')) => self.list.select_previous(),
+            (None, KeyCode::Down | KeyCode::Char('j')) => self.list.select_next(),
+            (None, KeyCode::PageUp) => self.list.scroll_up_by(10),
+            (None, KeyCode::PageDown) => self.list.scroll_down_by(10),
+            (None, KeyCode::Char('/')) if self.tab == Tab::Exclusions => self.input = Some(Input::Search),
+            (None, KeyCode::Char(' ')) if self.tab == Tab::Exclusions => self.toggle_check(false),
+            (None, KeyCode::Char('p')) if self.tab == Tab::Exclusions => self.toggle_check(true),
+            (None, KeyCode::Char(' ') | KeyCode::Right | KeyCode::Char('l')) => self.change(true),
+            (None, KeyCode::Left | KeyCode::Char('h')) => self.change(false),
+            (None, KeyCode::Char('s')) => match self.name.clone() {
+                Some(name) => self.save(name),
+                None => self.input = Some(Input::SaveAs(String::new())),
+            },
+            (None, KeyCode::Char('S')) => self.input = Some(Input::SaveAs(self.name.clone().unwrap_or_default())),
+            (None, KeyCode::Enter) if self.generate => match self.settings.validate() {
+                Ok(()) => return Some(true),
+                Err(err) => self.status = first(&err.to_string()),
+            },
+            _ => {}
+        }
+        // Keep the selection on the list as it shrinks
+        let len = self.len();
+        if self.list.selected().is_some_and(|selected| selected >= len) {
+            self.list.select(len.checked_sub(1));
+        }
+        None
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [tabs, body, description, footer] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(5),
+            Constraint::Length(4),
+            Constraint::Length(2),
+        ])
+        .areas(frame.area());
+
+        let title = match &self.name {
+            Some(name) => format!(" Z17 Randomizer settings: {} ", name),
+            None => " Z17 Randomizer settings ".to_string(),
+        };
+        let selected = Tab::ALL.iter().position(|tab| *tab == self.tab).unwrap_or_default();
+        frame.render_widget(
+            Tabs::new(Tab::ALL.iter().map(|tab| tab.title()))
+                .select(selected)
+                .highlight_style(Style::new().bold().reversed())
+                .block(Block::new().borders(Borders::ALL).title(title)),
+            tabs,
+        );
+
+        let highlight = Style::new().add_modifier(Modifier::REVERSED);
+        let (items, about) = if self.tab == Tab::Exclusions {
+            let checks = self.matching_checks();
+            let about = format!(
+                "{} excluded, {} prioritized. Excluded checks never hold progression, prioritized checks always do.",
+                self.settings.exclusions.exclusions.len(),
+                self.settings.exclusions.priority.len()
+            );
+            let items = checks
+                .iter()
+                .map(|(name, location)| {
+                    let name = name.to_string();
+                    let mark = if self.settings.exclusions.exclusions.contains(&name) {
+                        Span::raw("[x]").red()
+                    } else if self.settings.exclusions.priority.contains(&name) {
+                        Span::raw("[p]").green()
+                    } else {
+                        Span::raw("[ ]")
+                    };
+                    ListItem::new(Line::from(vec![
+                        mark,
+                        Span::raw(format!(" {:<8}{:<28}", format!("{:?}", location.world()), location.region())).dim(),
+                        Span::raw(name),
+                    ]))
+                })
+                .collect::<Vec<_>>();
+            (items, about)
+        } else {
+            let fields = self.fields_in(self.tab);
+            let about = self
+                .list
+                .selected()
+                .and_then(|selected| fields.get(selected))
+                .map(|field| field.description.clone())
+                .unwrap_or_default();
+            let items = fields
+                .iter()
+                .map(|field| {
+                    let label = field.name.replace('_', " ");
+                    let line = match (&field.kind, self.value(field)) {
+                        (Kind::Toggle, Value::Bool(true)) => Line::from(vec![Span::raw("[x] ").green(), Span::raw(label)]),
+                        (Kind::Toggle, _) => Line::from(format!("[ ] {}", label)),
+                        (Kind::Choice(_), value) => Line::from(vec![
+                            Span::raw(format!("    {}: ", label)),
+                            Span::raw(format!("< {} >", value.as_str().unwrap_or_default())).cyan(),
+                        ]),
+                    };
+                    ListItem::new(line)
+                })
+                .collect::<Vec<_>>();
+            (items, about)
+        };
+
+        let block = match (self.tab, &self.input) {
+            (Tab::Exclusions, Some(Input::Search)) => format!(" Search: {}_ ", self.search),
+            (Tab::Exclusions, _) if !self.search.is_empty() => format!(" Search: {} ", self.search),
+            _ => String::new(),
+        };
+        frame.render_stateful_widget(
+            List::new(items).highlight_style(highlight).block(Block::new().borders(Borders::ALL).title(block)),
+            body,
+            &mut self.list,
+        );
+        frame.render_widget(
+            Paragraph::new(about).wrap(Wrap { trim: true }).block(Block::new().borders(Borders::ALL)),
+            description,
+        );
+
+        let help = match (&self.input, self.tab) {
+            (Some(Input::SaveAs(name)), _) => format!("Save as preset: {}_   (Enter to save, Esc to cancel)", name),
+            (Some(Input::Search), _) => "Type to search, Enter or Esc when done".to_string(),
+            (None, Tab::Exclusions) => {
+                "↑↓ move  Space exclude  p prioritize  / search  Tab next section  s save  S save as".to_string()
+            }
+            (None, _) => "↑↓ move  Space/←→ change  Tab next section  s save  S save as".to_string(),
+        };
+        let leave = if self.generate { "  Enter generate  q quit" } else { "  q quit" };
+        frame.render_widget(
+            Paragraph::new(vec![Line::from(self.status.clone()).yellow(), Line::from(help + leave).dim()]),
+            footer,
+        );
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<bool> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    if let Some(done) = self.key(key.code, key.modifiers) {
+                        return Ok(done);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The first line of a multi-line message, noting how many more there are.
+fn first(message: &str) -> String {
+    let mut lines = message.lines();
+    let first = lines.next().unwrap_or_default().to_string();
+    match lines.count() {
+        0 => first,
+        more => format!("{} (and {} more)", first, more),
+    }
+}
+
+/// Opens the editor on `settings`, saving to the preset `name` if given, as extending the presets in `extends`.
+///
+/// With `generate`, Enter leaves the editor with the settings once they're valid. Returns the settings if they
+/// should be used, or `None` if the editor was quit instead, along with the name the preset was last saved as.
+pub fn edit(
+    system: &System<Settings>,
+    settings: Settings,
+    name: Option<String>,
+    extends: Vec<String>,
+    generate: bool,
+) -> io::Result<(Option<Settings>, Option<String>)> {
+    let mut editor = Editor {
+        system,
+        settings,
+        name,
+        extends,
+        generate,
+        fields: fields(),
+        checks: randomizer::checks(),
+        tab: Tab::Logic,
+        list: ListState::default().with_selected(Some(0)),
+        search: String::new(),
+        input: None,
+        status: String::new(),
+    };
+    let mut terminal = ratatui::try_init()?;
+    let done = editor.run(&mut terminal);
+    ratatui::restore();
+    Ok((done?.then_some(editor.settings), editor.name))
+}
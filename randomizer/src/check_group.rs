@@ -0,0 +1,261 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::check::Check;
+use crate::location::Location;
+use crate::location_node::LocationNode;
+use crate::regions;
+
+/// A category of checks that can be named in the exclusion and priority lists.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Tag {
+    Minigame,
+    Boss,
+    Npc,
+    Shop,
+    Ravio,
+    TreasureDungeon,
+}
+
+impl Tag {
+    pub fn iter() -> impl Iterator<Item=Self> {
+        [Self::Minigame, Self::Boss, Self::Npc, Self::Shop, Self::Ravio, Self::TreasureDungeon].into_iter()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Minigame => "minigame",
+            Self::Boss => "boss",
+            Self::Npc => "npc",
+            Self::Shop => "shop",
+            Self::Ravio => "ravio",
+            Self::TreasureDungeon => "treasure-dungeon",
+        }
+    }
+
+    /// Names of the checks carrying this tag
+    pub fn checks(&self) -> &'static [&'static str] {
+        match self {
+            Self::Minigame => &[
+                "Cucco Ranch",
+                "Hyrule Hotfoot",
+                "Rupee Rush (Hyrule)",
+                "Rupee Rush (Lorule)",
+                "Octoball Derby",
+                "Treacherous Tower (Intermediate)",
+            ],
+            Self::Boss => &[
+                "[EP] Yuga",
+                "[HoG] Margomill",
+                "[ToH] Moldorm",
+                "[PoD] Gemesaur King",
+                "[SP] Arrghus",
+                "[SW] Knucklemaster",
+                "Stalblind",
+                "[IR] Dharkstare",
+                "Zaganaga",
+                "[TR] Grinexx",
+                "[LC] Zelda",
+            ],
+            Self::Npc => &[
+                "Dampe",
+                "Rosso",
+                "Haunted Grove Tree Stump",
+                "Blacksmith",
+                "Shady Guy",
+                "Bee Guy",
+                "Bee Guy (Golden Bee)",
+                "Fortune Teller",
+                "Milk Bar Owner",
+                "Stylish Woman",
+                "Bird Lover",
+                "Zora Queen",
+                "Bouldering Guy",
+                "Runaway Item Seller",
+                "Blacksmith (Lorule)",
+                "Great Rupee Fairy",
+                "Hinox (1)",
+                "Hinox (2)",
+                "Hinox (3)",
+                "Hinox (4)",
+                "Hinox (5)",
+                "Hinox (6)",
+            ],
+            Self::Shop => &[
+                "Ravio (1)",
+                "Ravio (2)",
+                "Ravio (3)",
+                "Ravio (4)",
+                "Ravio (5)",
+                "Ravio (6)",
+                "Ravio (7)",
+                "Ravio (8)",
+                "Ravio (9)",
+                "Merchant (Left)",
+                "Merchant (Right)",
+            ],
+            Self::Ravio => &[
+                "Ravio (1)",
+                "Ravio (2)",
+                "Ravio (3)",
+                "Ravio (4)",
+                "Ravio (5)",
+                "Ravio (6)",
+                "Ravio (7)",
+                "Ravio (8)",
+                "Ravio (9)",
+            ],
+            Self::TreasureDungeon => &[
+                "Sanctuary Treasure Dungeon",
+                "Merge Treasure Dungeon",
+                "Treasure Room",
+                "Secret Cave",
+                "Hookshot Treasure Dungeon",
+                "Boots Treasure Dungeon",
+                "Sand Rod Treasure Dungeon",
+            ],
+        }
+    }
+}
+
+/// Dungeon abbreviations used as the prefix of every check name inside that dungeon.
+const DUNGEON_PREFIXES: [&str; 13] = [
+    "[HS]", "[EP]", "[HoG]", "[ToH]", "[LS]", "[PoD]", "[SP]", "[SW]", "[TH]", "[IR]", "[DP]", "[TR]", "[LC]",
+];
+
+/// Collects every check in the world graph that can hold a shuffled item.
+pub fn item_checks(world_graph: &HashMap<Location, LocationNode>) -> Vec<Check> {
+    world_graph.values()
+        .flat_map(|node| node.clone().get_checks())
+        .filter(|check| check.get_location_info().is_some())
+        .collect()
+}
+
+/// Resolves an entry from the exclusion or priority lists into the names of every check it covers.
+///
+/// An entry may be an exact check name, a region (e.g. `Lost Woods`), a world (`Hyrule`, `Lorule`,
+/// `Dungeons`), a dungeon abbreviation (e.g. `[EP]` or `EP`), or a tag (e.g. `minigame`).
+/// Matching ignores case. If nothing matches, the closest known names are returned as suggestions.
+pub fn resolve(name: &str, checks: &[Check]) -> Result<BTreeSet<&'static str>, Vec<String>> {
+    let name = name.trim();
+
+    if let Some(check) = checks.iter().find(|check| check.get_name().eq_ignore_ascii_case(name)) {
+        return Ok(BTreeSet::from([check.get_name()]));
+    }
+
+    if let Some(tag) = Tag::iter().find(|tag| tag.as_str().eq_ignore_ascii_case(name)) {
+        return Ok(tag.checks().iter().copied().collect());
+    }
+
+    let matched = checks.iter()
+        .filter(|check| {
+            let info = check.get_location_info().unwrap();
+            world_name(info.world()).eq_ignore_ascii_case(name)
+                || info.region().eq_ignore_ascii_case(name)
+                || dungeon_prefix(name).is_some_and(|prefix| check.get_name().starts_with(prefix))
+        })
+        .map(|check| check.get_name())
+        .collect::<BTreeSet<_>>();
+
+    if matched.is_empty() {
+        Err(suggest(name, checks))
+    } else {
+        Ok(matched)
+    }
+}
+
+fn world_name(world: regions::World) -> &'static str {
+    match world {
+        regions::World::Hyrule => "Hyrule",
+        regions::World::Lorule => "Lorule",
+        regions::World::Dungeons => "Dungeons",
+    }
+}
+
+/// Matches `[EP]` as well as the bare `EP` to a known dungeon prefix.
+fn dungeon_prefix(name: &str) -> Option<&'static str> {
+    DUNGEON_PREFIXES.iter().copied().find(|prefix| {
+        prefix.eq_ignore_ascii_case(name) || prefix[1..prefix.len() - 1].eq_ignore_ascii_case(name)
+    })
+}
+
+/// Finds up to three known names that are close to the unknown one.
+fn suggest(name: &str, checks: &[Check]) -> Vec<String> {
+    let mut candidates = checks.iter()
+        .flat_map(|check| {
+            let info = check.get_location_info().unwrap();
+            [check.get_name(), info.region(), world_name(info.world())]
+        })
+        .chain(Tag::iter().map(|tag| tag.as_str()))
+        .chain(DUNGEON_PREFIXES)
+        .collect::<Vec<_>>();
+    candidates.sort_unstable();
+    candidates.dedup();
+
+    let lowercase = name.to_lowercase();
+    let threshold = (name.chars().count() / 3).max(2);
+    let mut scored = candidates.into_iter()
+        .map(|candidate| (edit_distance(&lowercase, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .collect::<Vec<_>>();
+    scored.sort();
+    scored.into_iter().take(3).map(|(_, candidate)| candidate.to_string()).collect()
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::build_world_graph;
+
+    fn checks() -> Vec<Check> {
+        item_checks(&build_world_graph())
+    }
+
+    #[test]
+    fn it_resolves_exact_names() {
+        assert_eq!(resolve("master sword pedestal", &checks()), Ok(BTreeSet::from(["Master Sword Pedestal"])));
+    }
+
+    #[test]
+    fn it_resolves_groups() {
+        let checks = checks();
+        assert_eq!(resolve("minigame", &checks).unwrap().len(), Tag::Minigame.checks().len());
+        assert!(resolve("EP", &checks).unwrap().iter().all(|name| name.starts_with("[EP]")));
+        assert!(resolve("Lost Woods", &checks).unwrap().contains("Alcove"));
+        assert!(resolve("Lorule", &checks).unwrap().contains("Great Rupee Fairy"));
+    }
+
+    #[test]
+    fn it_knows_every_tagged_check() {
+        let checks = checks();
+        for tag in Tag::iter() {
+            for name in tag.checks() {
+                assert!(checks.iter().any(|check| check.get_name() == *name), "{} ({})", name, tag.as_str());
+            }
+        }
+    }
+
+    #[test]
+    fn it_suggests_close_names() {
+        assert!(resolve("Master Sword Pedstal", &checks()).unwrap_err().contains(&"Master Sword Pedestal".to_string()));
+    }
+}
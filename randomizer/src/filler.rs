@@ -1,7 +1,6 @@
 use std::collections::{HashMap, HashSet};
-use std::process::exit;
 
-use log::{error, info};
+use log::info;
 use queue::Queue;
 use rand::{Rng, SeedableRng};
 use rand::rngs::StdRng;
@@ -10,6 +9,7 @@ use albw::Item;
 
 use crate::{convert, LocationInfo, Seed, Settings};
 use crate::check::Check;
+use crate::check_group::{item_checks, resolve};
 use crate::FillerItem;
 use crate::FillerItem::*;
 use crate::location::Location;
@@ -38,123 +38,257 @@ pub fn fill_stuff(settings: &Settings, seed: Seed) -> Vec<(LocationInfo, Item)>
     let mut rng = StdRng::seed_from_u64(seed as u64);
 
     let mut world_graph = build_world_graph();
+    remove_unused_checks(&mut world_graph, settings);
     let mut check_map = prefill_check_map(&mut world_graph);
+    let checks = item_checks(&world_graph);
     let (mut progression_pool, mut trash_pool) = get_items(settings, &mut rng);
 
     verify_all_locations_accessible(&mut world_graph, &progression_pool, settings);
 
-    preplace_items(&mut check_map, settings, &mut rng, &mut progression_pool, &mut trash_pool);
+    // Settings are validated before filling, so only a randomly chosen placement rule item can fail here, and the
+    // panic has the seed retried
+    let mut problems = Vec::new();
+    let excluded = resolve_exclusions(&checks, settings, &mut problems);
+    let priority = resolve_priority(&checks, settings, &excluded, &mut problems);
 
-    assumed_fill(&mut world_graph, &mut rng, &mut progression_pool, &mut check_map, settings);
+    preplace_items(&mut check_map, &excluded, &mut rng, &mut progression_pool, &mut trash_pool);
+
+    let rules = resolve_rules(&check_map, &checks, settings, &mut rng, &progression_pool, &mut problems);
+    if !problems.is_empty() {
+        panic!("{}", problems.join("\n"));
+    }
+
+    assumed_fill(&mut world_graph, &mut rng, &mut progression_pool, &mut check_map, &priority, &rules, settings);
+
+    verify_priority_filled(&check_map, &priority);
 
     fill_trash(&mut check_map, &mut rng, &trash_pool);
 
     map_to_result(world_graph, check_map)
 }
 
-/// Place static items ahead of the randomly filled ones
-fn preplace_items<'a>(check_map: &mut HashMap<&'a str, Option<FillerItem>>,
-                      settings: &'a Settings,
-                      rng: &mut StdRng,
-                      progression: &mut Vec<FillerItem>,
-                      trash: &mut Vec<FillerItem>) {
-    handle_exclusions(check_map, settings, rng, trash);
+/// Finds every problem with the settings that would stop a seed from being filled, going through the same steps as
+/// the filler up to placing the first item
+pub fn validate(settings: &Settings) -> Vec<String> {
+    let mut rng = StdRng::seed_from_u64(0);
 
-    check_map.insert("Shore", Some(LetterInABottle));
-    progression.retain(|x| *x != LetterInABottle);
-
-    let mut shop_positions: Vec<&str> = Vec::new();
-    let mut lorule_castle_positions: Vec<&str> = Vec::new();
+    let mut world_graph = build_world_graph();
+    remove_unused_checks(&mut world_graph, settings);
+    let mut check_map = prefill_check_map(&mut world_graph);
+    let checks = item_checks(&world_graph);
+    let (mut progression_pool, mut trash_pool) = get_items(settings, &mut rng);
 
-    for (check_name, item) in check_map.clone() {
-        if check_name.starts_with("[LC]") && item.is_none() {
-            let _ = &lorule_castle_positions.push(check_name);
-        } else if check_name.starts_with("Ravio") && !check_name.contains("6") {
-            let _ = &shop_positions.push(check_name);
-        }
+    let mut problems = Vec::new();
+    let excluded = resolve_exclusions(&checks, settings, &mut problems);
+    let priority = resolve_priority(&checks, settings, &excluded, &mut problems);
+
+    let junk_needed = excluded.iter()
+        .filter(|check_name| *check_name != &"Shore" && check_map.get(*check_name).unwrap().is_none())
+        .count();
+    if junk_needed > trash_pool.len() {
+        problems.push(format!(
+            "{} checks are excluded, but there are only {} junk items to fill them with. Exclude fewer checks.",
+            junk_needed, trash_pool.len()));
+        return problems;
     }
 
-    if settings.logic.bow_of_light_in_castle {
-        check_map.insert(lorule_castle_positions.remove(rng.gen_range(0..lorule_castle_positions.len())), Some(BowOfLight));
-        progression.retain(|x| *x != BowOfLight);
+    preplace_items(&mut check_map, &excluded, &mut rng, &mut progression_pool, &mut trash_pool);
+
+    let priority_open = priority.iter()
+        .filter(|check_name| check_map.get(*check_name).unwrap().is_none())
+        .count();
+    let major_items = progression_pool.iter().filter(|item| is_major_item(**item)).count();
+    if priority_open > major_items {
+        problems.push(format!(
+            "{} checks are prioritized, but there are only {} major items to place in them. Prioritize fewer checks.",
+            priority_open, major_items));
     }
 
-    // Assures a weapon will be available in Ravio's Shop
-    if settings.logic.assured_weapon {
-        let mut weapons = Vec::from([
-            Bow01, Bombs01, FireRod01, IceRod01, Hammer01
-        ]);
+    resolve_rules(&check_map, &checks, settings, &mut rng, &progression_pool, &mut problems);
 
-        if !settings.logic.swordless_mode {
-            weapons.append(&mut Vec::from([Sword01, Sword02, Sword03, Sword04]));
-        }
+    problems
+}
 
-        match settings.logic.mode {
-            Normal => {}
-            _ => {
-                weapons.append(&mut Vec::from([Lamp01, Net01]));
-            }
+/// Drops the checks that the settings leave out of the seed
+fn remove_unused_checks(world_graph: &mut HashMap<Location, LocationNode>, settings: &Settings) {
+    if !settings.logic.nice_items {
+        for node in world_graph.values_mut() {
+            node.retain_checks(|check| !is_maiamai_offer(*check));
         }
+    }
+}
 
-        let weapon = *weapons.get(rng.gen_range(0..weapons.len())).unwrap();
+fn is_maiamai_offer(check: Check) -> bool {
+    check.get_name().starts_with("Mother Maiamai (")
+}
 
-        check_map.insert(shop_positions.remove(rng.gen_range(0..shop_positions.len())), Some(weapon));
-        progression.retain(|x| *x != weapon);
-    }
+/// Place static items ahead of the randomly filled ones
+fn preplace_items(check_map: &mut HashMap<&str, Option<FillerItem>>,
+                  excluded: &HashSet<&'static str>,
+                  rng: &mut StdRng,
+                  progression: &mut Vec<FillerItem>,
+                  trash: &mut Vec<FillerItem>) {
+    check_map.insert("Shore", Some(LetterInABottle));
+    progression.retain(|x| *x != LetterInABottle);
 
-    // if settings.logic.sword_in_shop {
-    //     check_map.insert(shop_positions.remove(rng.gen_range(0..shop_positions.len())), Some(Sword01));
-    //     progression.retain(|x| *x != Sword01);
-    // }
+    handle_exclusions(check_map, excluded, rng, trash);
+}
 
-    if settings.logic.bell_in_shop {
-        check_map.insert(shop_positions.remove(rng.gen_range(0..shop_positions.len())), Some(Bell));
-        progression.retain(|x| *x != Bell);
+/// Fills every excluded check that isn't already filled with junk
+fn handle_exclusions(check_map: &mut HashMap<&str, Option<FillerItem>>,
+                     excluded: &HashSet<&'static str>,
+                     rng: &mut StdRng,
+                     trash_pool: &mut Vec<FillerItem>) {
+    // Sorted so the same seed always excludes in the same order
+    let mut excluded = excluded.iter().copied().collect::<Vec<_>>();
+    excluded.sort_unstable();
+
+    for check_name in excluded {
+        if check_map.get(check_name).unwrap().is_none() {
+            check_map.insert(check_name, Some(trash_pool.remove(rng.gen_range(0..trash_pool.len()))));
+        }
     }
+}
 
-    if settings.logic.pouch_in_shop {
-        check_map.insert(shop_positions.remove(rng.gen_range(0..shop_positions.len())), Some(Pouch));
-        progression.retain(|x| *x != Pouch);
+/// Resolves the checks matched by the exclusion list, and the minigames if they're excluded
+fn resolve_exclusions(checks: &[Check], settings: &Settings, problems: &mut Vec<String>) -> HashSet<&'static str> {
+    let mut excluded = resolve_all("exclude", settings.exclusions.excluded(), checks, problems);
+    if settings.logic.minigames_excluded {
+        excluded.extend(resolve_all("exclude", [&String::from("minigame")], checks, problems));
     }
+    excluded
+}
 
-    if settings.logic.boots_in_shop {
-        check_map.insert(shop_positions.remove(rng.gen_range(0..shop_positions.len())), Some(PegasusBoots));
-        progression.retain(|x| *x != PegasusBoots);
+/// Resolves the priority list, ensuring none of its checks were also excluded
+fn resolve_priority(checks: &[Check],
+                    settings: &Settings,
+                    excluded: &HashSet<&'static str>,
+                    problems: &mut Vec<String>) -> HashSet<&'static str> {
+    let priority = resolve_all("prioritize", settings.exclusions.priority(), checks, problems);
+
+    let mut conflicts = priority.intersection(excluded).collect::<Vec<_>>();
+    conflicts.sort_unstable();
+    for check_name in conflicts {
+        problems.push(format!(
+            "\"{}\" is both excluded and prioritized, so it can't hold progression and must at the same time.",
+            check_name));
     }
 
-    if settings.logic.minigames_excluded {
-        exclude("Cucco Ranch", rng, check_map, trash);
-        exclude("Hyrule Hotfoot", rng, check_map, trash);
-        exclude("Rupee Rush (Hyrule)", rng, check_map, trash);
-        exclude("Rupee Rush (Lorule)", rng, check_map, trash);
-        exclude("Octoball Derby", rng, check_map, trash);
-        exclude("Treacherous Tower (Intermediate)", rng, check_map, trash);
-    }
+    priority
 }
 
-fn exclude(check_name: &'static str, rng: &mut StdRng, check_map: &mut HashMap<&str, Option<FillerItem>>, trash: &mut Vec<FillerItem>) {
-    check_map.insert(check_name, Some(trash.remove(rng.gen_range(0..trash.len()))));
+/// Resolves a list of check names, groups and tags, with suggestions for any entry that's unknown
+fn resolve_all<'a, I>(action: &str, names: I, checks: &[Check], problems: &mut Vec<String>) -> HashSet<&'static str>
+    where
+        I: IntoIterator<Item=&'a String>,
+{
+    let mut resolved = HashSet::new();
+
+    for name in names {
+        match resolve(name, checks) {
+            Ok(check_names) => resolved.extend(check_names),
+            Err(suggestions) => {
+                let mut problem = format!("Cannot {} \"{}\", no matching check, region or tag found with that name.", action, name);
+                if !suggestions.is_empty() {
+                    problem.push_str(&format!(" Did you mean: {}?", suggestions.iter().map(|s| format!("\"{}\"", s)).collect::<Vec<_>>().join(", ")));
+                }
+                problems.push(problem);
+            }
+        }
+    }
+
+    resolved
 }
 
+/// Resolves the placement rules into the checks each constrained item may be placed in, noting any that can't all
+/// be satisfied
+fn resolve_rules(check_map: &HashMap<&str, Option<FillerItem>>,
+                 checks: &[Check],
+                 settings: &Settings,
+                 rng: &mut StdRng,
+                 progression: &[FillerItem],
+                 problems: &mut Vec<String>) -> HashMap<FillerItem, HashSet<&'static str>> {
+    let mut allowed: HashMap<FillerItem, HashSet<&'static str>> = HashMap::new();
+    let mut order = Vec::new();
+
+    for rule in settings.placement_rules() {
+        let item = match (rule.item, rule.one_of.is_empty()) {
+            (Some(item), true) => item,
+            (None, false) => {
+                let candidates = rule.one_of.iter()
+                    .filter(|item| progression.contains(item) && !allowed.contains_key(item))
+                    .collect::<Vec<_>>();
+                if candidates.is_empty() {
+                    problems.push(format!(
+                        "None of the items {:?} can be placed by a rule, as none are shuffled with the current settings or they're all placed by other rules.",
+                        rule.one_of));
+                    continue;
+                }
+                *candidates[rng.gen_range(0..candidates.len())]
+            }
+            _ => {
+                problems.push("Placement rules need exactly one of \"item\" or \"one_of\".".to_string());
+                continue;
+            }
+        };
+
+        if !progression.contains(&item) {
+            problems.push(format!("Cannot place {:?} by a rule, it isn't shuffled with the current settings.", item));
+            continue;
+        }
 
-fn handle_exclusions<'a>(check_map: &mut HashMap<&'a str, Option<FillerItem>>,
-                         settings: &'a Settings,
-                         rng: &mut StdRng,
-                         trash_pool: &mut Vec<FillerItem>) {
-    let opt = settings.exclusions.0.get("exclusions");
-    if opt.is_none() {
-        return;
+        let entry = allowed.entry(item).or_insert_with(|| {
+            order.push(item);
+            checks.iter()
+                .filter(|check| check_map.get(check.get_name()).unwrap().is_none())
+                .map(|check| check.get_name())
+                .collect()
+        });
+        if !rule.within.is_empty() {
+            let within = resolve_all("place items in", &rule.within, checks, problems);
+            entry.retain(|check_name| within.contains(check_name));
+        }
+        let not_in = resolve_all("keep items out of", &rule.not_in, checks, problems);
+        entry.retain(|check_name| !not_in.contains(check_name));
     }
 
-    let exclusions = opt.unwrap();
+    for item in &order {
+        let checks_left = allowed.get_mut(item).unwrap();
+        if is_dungeon_item(*item) {
+            let mut eligible = checks.iter().filter(|check| checks_left.contains(check.get_name())).cloned().collect();
+            *checks_left = filter_dungeon_checks(*item, &mut eligible).iter().map(|check| check.get_name()).collect();
+        }
+        if checks_left.is_empty() {
+            problems.push(format!(
+                "No check is left for {:?} that satisfies every placement rule for it and isn't excluded.", item));
+        }
+    }
 
-    for exclusion in exclusions {
-        if check_map.contains_key(&exclusion.as_str()) {
-            check_map.insert(&exclusion.as_str(), Some(trash_pool.remove(rng.gen_range(0..trash_pool.len()))));
-        } else {
-            error!("Cannot exclude \"{}\", no matching check found with that name.", &exclusion.as_str());
-            error!("Consult a spoiler log for a list of valid check names.");
-            exit(1);
+    // Every group of items restricted to the same checks needs at least as many checks as items
+    for item in &order {
+        let checks_left = allowed.get(item).unwrap();
+        let competing = order.iter()
+            .filter(|other| allowed.get(other).unwrap().is_subset(checks_left))
+            .collect::<Vec<_>>();
+        if !checks_left.is_empty() && competing.len() > checks_left.len() {
+            let problem = format!(
+                "Placement rules require {} items ({:?}) in only {} checks that aren't excluded.",
+                competing.len(), competing, checks_left.len());
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+    }
+
+    allowed
+}
+
+/// Panics if a priority check didn't receive a progression item, so that the seed is retried
+fn verify_priority_filled(check_map: &HashMap<&str, Option<FillerItem>>, priority: &HashSet<&'static str>) {
+    for check_name in priority {
+        match check_map.get(check_name).unwrap() {
+            Some(item) if is_major_item(*item) => {}
+            _ => panic!("Priority check \"{}\" did not receive a progression item", check_name),
         }
     }
 }
@@ -511,6 +645,13 @@ pub fn get_items(settings: &Settings, rng: &mut StdRng) -> (Vec<FillerItem>, Vec
         trash.push(MonsterTail);
     }
 
+    // Nice Items, taking the place of Mother Maiamai's offers
+    if settings.logic.nice_items {
+        progression.extend([
+            Bow02, Boomerang02, Hookshot02, Bombs02, FireRod02, IceRod02, Hammer02, SandRod02, TornadoRod02,
+        ]);
+    }
+
     (shuffle_items(progression, rng), shuffle_items(trash, rng))
 }
 
@@ -590,6 +731,11 @@ fn is_dungeon_item(item: FillerItem) -> bool {
     }
 }
 
+/// Progression that isn't a dungeon item or rupee, the kind of item a priority check should hold
+fn is_major_item(item: FillerItem) -> bool {
+    !is_dungeon_item(item) && !matches!(convert(item), Some(Item::RupeePurple | Item::RupeeSilver | Item::RupeeGold))
+}
+
 fn fill_trash(check_map: &mut HashMap<&str, Option<FillerItem>>, rng: &mut StdRng, trash_items: &Vec<FillerItem>) {
     info!("Placing Junk Items...");
 
@@ -601,8 +747,7 @@ fn fill_trash(check_map: &mut HashMap<&str, Option<FillerItem>>, rng: &mut StdRn
     }
 
     if empty_check_keys.len() != trash_items.len() {
-        error!("There are {} empty checks and {} trash items", empty_check_keys.len(), trash_items.len());
-        exit(1);
+        panic!("There are {} empty checks and {} trash items", empty_check_keys.len(), trash_items.len());
     }
 
     for trash in trash_items {
@@ -663,8 +808,7 @@ pub fn prefill_check_map(world_graph: &mut HashMap<Location, LocationNode>) -> H
                 None => None,
                 Some(quest) => Some(quest) // Quest items are static so just set them right away
             }).is_some() {
-                error!("Multiple checks have duplicate name: {}", check.get_name());
-                exit(1);
+                panic!("Multiple checks have duplicate name: {}", check.get_name());
             }
         }
     }
@@ -692,14 +836,15 @@ fn verify_all_locations_accessible(loc_map: &mut HashMap<Location, LocationNode>
     let reachable_checks = assumed_search(loc_map, progression_pool, &mut check_map, settings); //find_reachable_checks(loc_map, &everything, &mut check_map); //
 
     const TOTAL_CHECKS: usize = 276; // all checks + quest checks
-    if reachable_checks.len() != TOTAL_CHECKS {
+    const MAIAMAI_OFFERS: usize = 9;
+    let total_checks = if settings.logic.nice_items { TOTAL_CHECKS + MAIAMAI_OFFERS } else { TOTAL_CHECKS };
+    if reachable_checks.len() != total_checks {
 
         // for rc in &reachable_checks {
         //     info!("Reachable Check: {}", rc.get_name());
         // }
 
-        error!("Only {}/{} checks were reachable in the world graph", reachable_checks.len(), TOTAL_CHECKS);
-        exit(1);
+        panic!("Only {}/{} checks were reachable in the world graph", reachable_checks.len(), total_checks);
     }
 }
 
@@ -718,10 +863,7 @@ fn find_reachable_checks(loc_map: &mut HashMap<Location, LocationNode>, progress
         // Grab the location from the map, verify it is defined
         let location_node = match loc_map.get_mut(&location) {
             Some(loc) => loc,
-            None => {
-                info!("Location Undefined: {:?}", location);
-                exit(1);
-            }
+            None => panic!("Location Undefined: {:?}", location),
         };
 
         // Iterate over the location's checks
@@ -772,13 +914,22 @@ fn assumed_fill(mut world_graph: &mut HashMap<Location, LocationNode>,
                 mut rng: &mut StdRng,
                 items_owned: &mut Vec<FillerItem>,
                 mut check_map: &mut HashMap<&str, Option<FillerItem>>,
+                priority: &HashSet<&'static str>,
+                rules: &HashMap<FillerItem, HashSet<&'static str>>,
                 settings: &Settings) {
     info!("Placing Progression Items...");
 
     let mut reachable_checks = assumed_search(&mut world_graph, &items_owned, &mut check_map, settings);
 
     while exist_empty_reachable_check(&reachable_checks, &check_map) && !items_owned.is_empty() {
-        let item = items_owned.remove(rng.gen_range(0..items_owned.len()));
+        // Items constrained by a placement rule go first, while the most checks are still reachable
+        let constrained = (0..items_owned.len()).filter(|&i| rules.contains_key(&items_owned[i])).collect::<Vec<_>>();
+        let index = if constrained.is_empty() {
+            rng.gen_range(0..items_owned.len())
+        } else {
+            constrained[rng.gen_range(0..constrained.len())]
+        };
+        let item = items_owned.remove(index);
         reachable_checks = assumed_search(&mut world_graph, &items_owned, &mut check_map, settings);
 
 
@@ -789,6 +940,15 @@ fn assumed_fill(mut world_graph: &mut HashMap<Location, LocationNode>,
             filtered_checks = filter_dungeon_checks(item, &mut filtered_checks);
         }
 
+        if let Some(allowed) = rules.get(&item) {
+            filtered_checks.retain(|check| allowed.contains(check.get_name()));
+        }
+
+        // Major items go to reachable priority checks first
+        if is_major_item(item) && filtered_checks.iter().any(|check| priority.contains(check.get_name())) {
+            filtered_checks.retain(|check| priority.contains(check.get_name()));
+        }
+
         if filtered_checks.len() == 0 {
             info!("No reachable checks found to place: {:?}", item);
         }
@@ -831,3 +991,81 @@ pub fn assumed_search(loc_map: &mut HashMap<Location, LocationNode>,
 
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_honors_placement_rules() {
+        let settings: Settings = toml::from_str(r#"
+            [logic]
+            boots_in_shop = true
+
+            [[rules]]
+            item = "Bell"
+            in = ["[EP]"]
+
+            [[rules]]
+            item = "Hookshot01"
+            not_in = ["Hyrule"]
+        "#).unwrap();
+
+        let placed = |result: &[(LocationInfo, Item)], item| {
+            result.iter().find(|(_, placed)| *placed == item).unwrap().0
+        };
+        // Like the CLI, skip seeds the filler gives up on
+        let results = (0..20)
+            .filter_map(|seed| std::panic::catch_unwind(|| fill_stuff(&settings, seed)).ok())
+            .take(3)
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        for result in results {
+            assert!(placed(&result, Item::ItemBell).name().starts_with("[EP]"));
+            assert!(placed(&result, Item::DashBoots).name().starts_with("Ravio"));
+            assert_ne!(placed(&result, Item::DashBoots).name(), "Ravio (6)");
+            assert_ne!(placed(&result, Item::ItemHookShot).world(), crate::regions::World::Hyrule);
+        }
+    }
+
+    #[test]
+    fn it_validates_settings() {
+        assert!(Settings::default().validate().is_ok());
+
+        let problems = |toml| {
+            let settings: Settings = toml::from_str(toml).unwrap();
+            settings.validate().unwrap_err().problems().to_vec()
+        };
+        assert!(problems(r#"exclusions = { exclusions = ["Nowhere"] }"#)[0].starts_with("Cannot exclude \"Nowhere\""));
+        assert!(problems(r#"exclusions = { exclusions = ["Hyrule", "Lorule"] }"#)[0].contains("junk items"));
+        assert!(problems(r#"exclusions = { exclusions = ["Dampe"], priority = ["Dampe"] }"#)[0].contains("both excluded and prioritized"));
+        assert!(problems(r#"exclusions = { priority = ["Hyrule", "Lorule"] }"#)[0].contains("major items"));
+        assert!(problems("logic = { bow_of_light_in_castle = true }\nexclusions = { exclusions = [\"[LC]\"] }")[0].contains("BowOfLight"));
+        // Every shop rule at once, with all but two of Ravio's slots excluded
+        let shop = problems(r#"
+            logic = { assured_weapon = true, bell_in_shop = true, pouch_in_shop = true, boots_in_shop = true }
+            exclusions = { exclusions = ["Ravio (1)", "Ravio (2)", "Ravio (3)", "Ravio (4)", "Ravio (5)", "Ravio (7)"] }
+        "#);
+        assert!(shop.iter().any(|problem| problem.contains("in only")), "{:?}", shop);
+    }
+
+    #[test]
+    fn it_shuffles_nice_items() {
+        let settings: Settings = toml::from_str("[logic]\nnice_items = true").unwrap();
+        let result = (0..20)
+            .find_map(|seed| std::panic::catch_unwind(|| fill_stuff(&settings, seed)).ok())
+            .unwrap();
+        assert_eq!(result.iter().filter(|(_, item)| *item == Item::ItemBow).count(), 2);
+        let offers = result.iter().filter(|(location, _)| location.name().starts_with("Mother Maiamai (")).collect::<Vec<_>>();
+        assert_eq!(offers.len(), 9);
+    }
+
+    #[test]
+    fn it_leaves_maiamai_offers_out_without_nice_items() {
+        let settings: Settings = toml::from_str("[logic]\nmode = \"Hard\"").unwrap();
+        let result = (0..20)
+            .find_map(|seed| std::panic::catch_unwind(|| fill_stuff(&settings, seed)).ok())
+            .unwrap();
+        assert!(result.iter().all(|(location, _)| !location.name().starts_with("Mother Maiamai (")));
+    }
+}
@@ -1,8 +1,10 @@
 use albw::Item;
 use albw::Item::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use FillerItem::*;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, JsonSchema, Serialize)]
 pub enum FillerItem {
     Bow01,
     Bow02,
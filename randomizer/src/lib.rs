@@ -1,6 +1,5 @@
-use std::{collections::BTreeMap, error::Error as StdError, fs, fs::File, io};
-use std::io::{stdin, stdout, Write};
-use std::path::Path;
+use std::{collections::BTreeMap, error::Error as StdError, fmt::{self, Display, Formatter}, fs::File, io};
+use std::path::PathBuf;
 
 use linked_hash_map::LinkedHashMap;
 use log::{debug, info};
@@ -9,6 +8,8 @@ use serde::{ser::SerializeMap, Serialize, Serializer};
 use albw::{Game, Item};
 use albw::Item::*;
 use patch::Patcher;
+pub use patch::{CodeFormat, OutputFormat};
+pub use seed_file::SeedFile;
 use regions::Subregion;
 pub use settings::Settings;
 use state::State;
@@ -17,13 +18,16 @@ use sys::{Paths, System};
 use crate::filler::fill_stuff;
 use crate::filler_item::{convert, FillerItem};
 use crate::settings::plando_settings;
+use crate::world::build_world_graph;
 
 mod graph;
 mod patch;
 mod regions;
+pub mod seed_file;
 pub mod settings;
 mod state;
 mod check;
+mod check_group;
 pub mod filler_item;
 mod loading_zone;
 mod loading_zone_pair;
@@ -49,18 +53,15 @@ impl Error {
         where
             S: Into<Box<dyn StdError + Send + Sync + 'static>>,
     {
-        Self {
-            kind: ErrorKind::Game,
-            inner: err.into(),
-        }
+        Self::with_kind(ErrorKind::Game, err)
     }
 
-    fn io<S>(err: S) -> Self
+    fn with_kind<S>(kind: ErrorKind, err: S) -> Self
         where
             S: Into<Box<dyn StdError + Send + Sync + 'static>>,
     {
         Self {
-            kind: ErrorKind::Io,
+            kind,
             inner: err.into(),
         }
     }
@@ -80,7 +81,12 @@ impl From<albw::Error> for Error {
     fn from(err: albw::Error) -> Self {
         let kind = match err.kind() {
             albw::ErrorKind::Io => ErrorKind::Io,
-            albw::ErrorKind::Rom => ErrorKind::Game,
+            albw::ErrorKind::RomNotFound => ErrorKind::RomNotFound,
+            albw::ErrorKind::Format
+            | albw::ErrorKind::Encrypted
+            | albw::ErrorKind::Region
+            | albw::ErrorKind::Checksum => ErrorKind::InvalidRom(err.kind()),
+            albw::ErrorKind::Rom | albw::ErrorKind::NotFound => ErrorKind::Game,
         };
         Self {
             kind,
@@ -98,6 +104,27 @@ impl From<io::Error> for Error {
     }
 }
 
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self.kind {
+            ErrorKind::Io => Some(self.inner.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<settings::InvalidSettings> for Error {
+    fn from(err: settings::InvalidSettings) -> Self {
+        Self::with_kind(ErrorKind::InvalidSettings, err)
+    }
+}
+
 impl From<sys::Error> for Error {
     fn from(err: sys::Error) -> Self {
         Self {
@@ -107,11 +134,22 @@ impl From<sys::Error> for Error {
     }
 }
 
-#[derive(Debug)]
+/// The kind of error contained within the `Error` type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ErrorKind {
+    /// The configuration or presets couldn't be loaded.
     Sys,
+    /// The game's data couldn't be patched.
     Game,
+    /// The settings can't be used to generate a seed, for the reasons listed in the message.
+    InvalidSettings,
     Io,
+    /// There's no ROM at the configured path.
+    RomNotFound,
+    /// The ROM was found but can't be used, for the reason given by the inner kind.
+    InvalidRom(albw::ErrorKind),
+    /// The patched game couldn't be written to the output directory.
+    OutputNotWritable,
 }
 
 pub type Seed = u32;
@@ -258,7 +296,12 @@ fn serialize_world<S>(region: &World, ser: S) -> Result<S::Ok, S::Error>
 }
 
 fn item_to_str(item: &Item) -> &'static str {
-    match item {
+    item_name(item).unwrap_or_else(|| unreachable!("{}", item.as_str()))
+}
+
+/// The name the spoiler log uses for an item, if it can be placed.
+fn item_name(item: &Item) -> Option<&'static str> {
+    Some(match item {
         KeySmall => "Small Key",
         KeyBoss => "Big Key",
         Compass => "Compass",
@@ -368,8 +411,8 @@ fn item_to_str(item: &Item) -> &'static str {
         LoruleCastleKeySmall => "Lorule Castle Small Key",
         LoruleCastleCompass => "Lorule Castle Compass",
 
-        _ => unreachable!("{}", item.as_str()),
-    }
+        _ => return None,
+    })
 }
 
 trait ItemExt {
@@ -528,13 +571,33 @@ pub struct Spoiler<'settings> {
 }
 
 impl<'settings> Spoiler<'settings> {
-    pub fn patch(self, paths: Paths, patch: bool, spoiler: bool) -> Result<()> {
-        let game = Game::load(paths.rom())?;
-        let mut patcher = Patcher::new(game)?;
-        regions::patch(&mut patcher, &self.layout, self.settings)?;
-        let patches = patcher.prepare(self.settings)?;
+    /// Describes this seed without any game data, so it can be shared and applied to another copy of the ROM.
+    pub fn seed_file(&self) -> SeedFile {
+        SeedFile::new(self.seed, self.settings, &self.layout)
+    }
+
+    /// Writes the patches and seed file if `patch` is set, and the spoiler log if `spoiler` is set.
+    ///
+    /// The ROM is only read when patching, so a spoiler log alone can be written without one.
+    pub fn patch(self, paths: Paths, patch: bool, spoiler: bool, format: OutputFormat) -> Result<()> {
         if patch {
-            patches.dump(paths.output())?;
+            let game = if paths.verify_rom() {
+                Game::load(paths.rom())?
+            } else {
+                Game::load_unverified(paths.rom())?
+            };
+            let mut patcher = Patcher::new(game, self.settings)?;
+            regions::patch(&mut patcher, &self.layout, self.settings)?;
+            if self.settings.options.enemy_shuffle {
+                patcher.shuffle_enemies(self.seed)?;
+            }
+            let patches = patcher.prepare(self.settings)?;
+            patches.write(paths.output(), format)?;
+            let seed_file = self.seed_file();
+            let path = paths.output().join(format!("seed {}.z17r", self.seed));
+            info!("Writing seed file to:           {}", path.display());
+            info!("Seed hash:                      {}", seed_file.hash());
+            seed_file.write(path)?;
         }
         if spoiler {
             let path = paths.output().join(format!("spoiler {}.json", self.seed));
@@ -547,53 +610,35 @@ impl<'settings> Spoiler<'settings> {
     }
 }
 
-/// Gets the system object for the platform.
-pub fn system() -> sys::Result<System<Settings>> {
-    System::new()
+/// Lists every check an item can be placed at, sorted by world and region, by the names that exclusions, placement
+/// rules and seed files use.
+pub fn checks() -> Vec<(&'static str, LocationInfo)> {
+    let mut checks = build_world_graph()
+        .into_values()
+        .flat_map(|node| node.get_checks())
+        .filter_map(|check| Some((check.get_name(), check.get_location_info()?)))
+        .collect::<Vec<_>>();
+    checks.sort_by_key(|(name, location)| (location.world() as u8, location.region(), *name));
+    checks
 }
 
-#[cfg(test)]
-pub fn test_game() -> albw::Result<Game> {
-    Game::load("../test.3ds")
-}
+/// Presets built into the randomizer, which are written to the presets folder on first run.
+const PRESETS: &[(&str, &str)] = &[("Example", include_str!("../../presets/Example.toml"))];
 
-fn prompt_until<F>(prompt: &str, until: F, error: &str) -> sys::Result<String>
-    where
-        F: Fn(&str) -> bool,
-{
-    loop {
-        print!("{}: ", prompt);
-        stdout().flush()?;
-        let mut input = String::new();
-        stdin().read_line(&mut input)?;
-        input = input.trim().to_string();
-        if until(&input) {
-            break Ok(input);
-        } else {
-            eprintln!("{}", error);
-        }
-    }
+/// Gets the system object for the platform, using `config` as the config directory if given.
+pub fn system(config: Option<PathBuf>) -> sys::Result<System<Settings>> {
+    System::new(config, PRESETS)
 }
 
-fn create_paths() -> sys::Result<Paths> {
-    let rom = prompt_until(
-        "Path to ROM",
-        |rom| Game::load(&rom).is_ok(),
-        "The provided path does not point to a valid ROM.",
-    )?;
-    let output = prompt_until(
-        "Output directory",
-        |output| Path::new(output).exists() || fs::create_dir_all(&output).is_ok(),
-        "The provided path could not be created.",
-    )?;
-
-    Ok(Paths::new(rom.into(), output.into()))
+#[cfg(test)]
+pub fn test_game() -> albw::Result<Game> {
+    Game::load("../test.3ds")
 }
 
-pub fn plando() -> Result<(), Error> {
+/// Writes a hand-placed seed for testing to the given paths.
+pub fn plando(paths: Paths) -> Result<(), Error> {
     info!("Start the Plando!");
 
-    let system = system()?;
     let settings = plando_settings();
     let mut layout = Layout::default();
 
@@ -928,12 +973,16 @@ pub fn plando() -> Result<(), Error> {
     };
 
     spoiler.patch(
-        system.get_or_create_paths(create_paths)?,
+        paths,
         true,
         true,
+        OutputFormat::default(),
     )
 }
 
+/// Fills a seed, panicking if it can't be completed so that another seed can be tried.
+///
+/// The settings should pass [`Settings::validate`] first, or every seed will fail.
 pub fn filler_new(settings: &Settings, seed: Seed) -> Spoiler {
 
     // New Filler
@@ -32,6 +32,7 @@ pub enum Location {
     HyruleCastleRoof,
     HyruleCastleDungeon,
     LostWoods,
+    MaiamaiCave,
     MasterSwordArea,
     FortuneTeller,
     KakarikoJailCell,
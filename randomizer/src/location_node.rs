@@ -16,6 +16,13 @@ impl LocationNode {
         self.checks
     }
 
+    pub fn retain_checks<F>(&mut self, f: F)
+        where
+            F: FnMut(&Check) -> bool,
+    {
+        self.checks.retain(f);
+    }
+
     pub fn get_paths(self) -> Vec<Path> {
         self.paths
     }
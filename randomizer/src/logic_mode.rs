@@ -1,6 +1,9 @@
+use std::str::FromStr;
+
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, JsonSchema, Serialize)]
 pub enum LogicMode {
     Normal,
     Hard,
@@ -14,4 +17,37 @@ impl Default for LogicMode {
     fn default() -> Self {
         LogicMode::Normal
     }
-}
\ No newline at end of file
+}
+impl LogicMode {
+    /// Iterates over every logic mode, from the most to the least restrictive.
+    pub fn iter() -> impl Iterator<Item = Self> {
+        [Self::Normal, Self::Hard, Self::GlitchBasic, Self::GlitchAdvanced, Self::GlitchHell, Self::NoLogic].into_iter()
+    }
+}
+
+impl FromStr for LogicMode {
+    type Err = String;
+
+    /// Parses a mode by its name in a preset, ignoring case, dashes and underscores (`glitch-basic`, `NoLogic`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.replace(['-', '_'], "").to_ascii_lowercase();
+        Self::iter()
+            .find(|mode| format!("{:?}", mode).to_ascii_lowercase() == name)
+            .ok_or_else(|| {
+                format!("Unknown logic mode: {}. Expected normal, hard, glitch-basic, glitch-advanced, glitch-hell or no-logic.", s)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_modes() {
+        assert_eq!("normal".parse(), Ok(LogicMode::Normal));
+        assert_eq!("glitch-basic".parse(), Ok(LogicMode::GlitchBasic));
+        assert_eq!("NoLogic".parse(), Ok(LogicMode::NoLogic));
+        assert!("glitched".parse::<LogicMode>().is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! Where the code patches go in each release's code binary.
+//!
+//! Every address the patches use is listed here, so supporting another release only takes a new entry in
+//! [`RELEASES`]. Only the North American release's addresses are known so far.
+
+use albw::{Item, Region};
+
+use crate::{Error, Result};
+
+/// The addresses of one release.
+#[derive(Debug)]
+pub struct Addresses {
+    /// Title ID of the release, as in [`Region::id`].
+    pub id: u64,
+
+    // Globals
+    pub event_flags: u32,
+    pub player_data: u32,
+    pub item_data: u32,
+    pub vtable_string: u32,
+
+    // Functions
+    pub set_event_flag: u32,
+    pub get_event_flag: u32,
+    pub get_event_value: u32,
+    pub get_player_flag: u32,
+    pub set_player_flag: u32,
+    pub get_player_value: u32,
+    pub get_item_value: u32,
+    pub set_item_value: u32,
+    pub map_rental_item: u32,
+
+    // Patch sites, and where they return to
+    pub y_button: u32,
+    pub text_speed: u32,
+    pub master_sword_flag: u32,
+    pub get_item_end: u32,
+    pub bow_of_light_defeat: u32,
+    pub blacksmith: [u32; 2],
+    pub rental_actors: u32,
+    pub rental_actors_end: u32,
+    pub rental_names: u32,
+    pub rental_items: u32,
+    pub rental_getters: [u32; 5],
+    pub rental_setter: u32,
+    pub rental_setter_end: u32,
+    pub default_item_name: u32,
+    pub sold_out: u32,
+    pub merchant_actors: [u32; 2],
+    pub merchant_items: [u32; 2],
+    pub merchant_flag: u32,
+    pub heart_flag: u32,
+    pub key_flag: u32,
+    pub premium_milk: u32,
+    pub no_inventory: [u32; 2],
+    pub progressive_items: u32,
+    pub progressive_items_end: u32,
+    pub ring_hekiga: u32,
+    pub is_ring_hekiga: [u32; 3],
+    pub ore_progress: u32,
+
+    /// Actor names already in the code, for items that have one.
+    pub actor_names: &'static [(Item, u32)],
+    /// Item message labels already in the code, for items that have one.
+    pub item_names: &'static [(Item, u32)],
+}
+
+impl Addresses {
+    /// Finds the addresses for the given release.
+    pub fn for_region(region: &Region) -> Result<&'static Self> {
+        RELEASES.iter().find(|addresses| addresses.id == region.id).ok_or_else(|| {
+            Error::game(format!(
+                "Code patches are only available for the North American release, not {}.",
+                region.name
+            ))
+        })
+    }
+}
+
+/// Every release whose code the patches have been found in.
+static RELEASES: &[Addresses] = &[Addresses {
+    id: 0x00040000000EC300,
+
+    event_flags: 0x70B728,
+    player_data: 0x70C8E0,
+    item_data: 0x70FB60,
+    vtable_string: 0x6F5988,
+
+    set_event_flag: 0x4CDF40,
+    get_event_flag: 0x584B94,
+    get_event_value: 0x584A80,
+    get_player_flag: 0x5822A0,
+    set_player_flag: 0x4AD9E8,
+    get_player_value: 0x52A05C,
+    get_item_value: 0x55696C,
+    set_item_value: 0x255494,
+    map_rental_item: 0x194BFC,
+
+    y_button: 0x47B2C8,
+    text_speed: 0x17A430,
+    master_sword_flag: 0x344E50,
+    get_item_end: 0x344F00,
+    bow_of_light_defeat: 0x502DD8,
+    blacksmith: [0x243DE8, 0x30E160],
+    rental_actors: 0x5D688C,
+    rental_actors_end: 0x5D68F4,
+    rental_names: 0x714608,
+    rental_items: 0x6A0348,
+    rental_getters: [0x194728, 0x311CE4, 0x311EAC, 0x31261C, 0x312660],
+    rental_setter: 0x652E34,
+    rental_setter_end: 0x652E70,
+    default_item_name: 0x6F9B1A,
+    sold_out: 0x5D6B84,
+    merchant_actors: [0x707DD4, 0x707DE0],
+    merchant_items: [0x6A03E0, 0x6A03E8],
+    merchant_flag: 0x19487C,
+    heart_flag: 0x33497C,
+    key_flag: 0x192E58,
+    premium_milk: 0x3455C4,
+    no_inventory: [0x345580, 0x3455C0],
+    progressive_items: 0x2922A0,
+    progressive_items_end: 0x2922C4,
+    ring_hekiga: 0x3448F4,
+    is_ring_hekiga: [0x1DCA8C, 0x4266D0, 0x52E654],
+    ore_progress: 0x4637B8,
+
+    actor_names: &[
+        (Item::ItemStoneBeauty, 0x5D2060),
+        (Item::RupeeR, 0x5D639C),
+        (Item::RupeeG, 0x5D639C),
+        (Item::RupeeB, 0x5D639C),
+        (Item::RupeePurple, 0x5D639C),
+        (Item::RupeeSilver, 0x5D63A4),
+        (Item::KeySmall, 0x5D6580),
+        (Item::ItemIceRod, 0x5D6AFC),
+        (Item::ItemSandRod, 0x5D6B08),
+        (Item::ItemTornadeRod, 0x5D6B18),
+        (Item::ItemBomb, 0x5D6B28),
+        (Item::ItemFireRod, 0x5D6B30),
+        (Item::ItemHookShot, 0x5D6B40),
+        (Item::ItemBoomerang, 0x5D6B50),
+        (Item::ItemHammer, 0x5D6B60),
+        (Item::ItemBow, 0x5D6B6C),
+        (Item::ItemShield, 0x5D6B78),
+        (Item::ItemBottle, 0x5D7048),
+        (Item::HintGlasses, 0x5D70AC),
+        (Item::RupeeGold, 0x5D7144),
+        (Item::ItemSwordLv2, 0x5D7178),
+        (Item::LiverPurple, 0x5D762C),
+        (Item::LiverYellow, 0x5D7640),
+        (Item::LiverBlue, 0x5D7654),
+        (Item::MessageBottle, 0x5D76A0),
+        (Item::Pouch, 0x5D7734),
+        (Item::ItemBowLight, 0x5D776C),
+        (Item::HeartContainer, 0x5D7B7C),
+        (Item::HeartPiece, 0x5D7B94),
+    ],
+    item_names: &[
+        (Item::ItemBomb, 0x6F9A9A),
+        (Item::ItemBombLv2, 0x6F9A9A),
+        (Item::ItemSandRod, 0x6F9AD0),
+        (Item::ItemSandRodLv2, 0x6F9AD0),
+        (Item::ItemIceRod, 0x6F9AE2),
+        (Item::ItemIceRodLv2, 0x6F9AE2),
+        (Item::ItemTornadeRod, 0x6F9AF3),
+        (Item::ItemTornadeRodLv2, 0x6F9AF3),
+        (Item::ItemFireRod, 0x6F9B08),
+        (Item::ItemFireRodLv2, 0x6F9B08),
+        (Item::LiverPurple, 0x6F9B55),
+        (Item::ItemBottle, 0x6F9B6C),
+        (Item::LiverBlue, 0x6F9B94),
+        (Item::ItemBoomerang, 0x6F9BA9),
+        (Item::ItemBoomerangLv2, 0x6F9BA9),
+        (Item::ItemHammer, 0x6F9CCC),
+        (Item::ItemHammerLv2, 0x6F9CCC),
+        (Item::ItemHookShot, 0x6F9CDD),
+        (Item::ItemHookShotLv2, 0x6F9CDD),
+        (Item::ItemBow, 0x6F9D08),
+        (Item::ItemBowLv2, 0x6F9D08),
+        (Item::LiverYellow, 0x6F9D2F),
+        (Item::ItemStoneBeauty, 0x6F9D56),
+    ],
+}];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_finds_addresses_for_supported_releases() {
+        for region in Region::iter() {
+            assert!(Addresses::for_region(region).is_ok(), "no addresses for {}", region.name);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Writing BPS patches, which unlike IPS have no limits on record length or offset.
+
+use crc::crc32::checksum_ieee;
+
+/// Encodes a patch that turns `source` into `target`.
+///
+/// Bytes that are unchanged at the same offset are read from the source, and everything else is stored as is.
+pub fn encode(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut patch = b"BPS1".to_vec();
+    write_number(&mut patch, source.len() as u64);
+    write_number(&mut patch, target.len() as u64);
+    write_number(&mut patch, 0);
+    let unchanged = |i: usize| source.get(i) == target.get(i);
+    let mut start = 0;
+    while start < target.len() {
+        let copy = unchanged(start);
+        let end = (start..target.len())
+            .find(|&i| unchanged(i) != copy)
+            .unwrap_or(target.len());
+        let mode = if copy { SOURCE_READ } else { TARGET_READ };
+        write_number(&mut patch, ((end - start - 1) as u64) << 2 | mode);
+        if !copy {
+            patch.extend_from_slice(&target[start..end]);
+        }
+        start = end;
+    }
+    patch.extend_from_slice(&checksum_ieee(source).to_le_bytes());
+    patch.extend_from_slice(&checksum_ieee(target).to_le_bytes());
+    let checksum = checksum_ieee(&patch);
+    patch.extend_from_slice(&checksum.to_le_bytes());
+    patch
+}
+
+/// Writes a variable-length number, where each byte carries 7 bits and the high bit marks the last one.
+fn write_number(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let bits = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(0x80 | bits);
+            break;
+        }
+        buf.push(bits);
+        value -= 1;
+    }
+}
+
+const SOURCE_READ: u64 = 0;
+const TARGET_READ: u64 = 1;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_number(patch: &mut &[u8]) -> u64 {
+        let (mut value, mut shift) = (0, 1);
+        loop {
+            let byte = patch[0] as u64;
+            *patch = &patch[1..];
+            value += (byte & 0x7F) * shift;
+            if byte & 0x80 != 0 {
+                return value;
+            }
+            shift <<= 7;
+            value += shift;
+        }
+    }
+
+    fn apply(source: &[u8], patch: &[u8]) -> Vec<u8> {
+        let (mut actions, footer) = patch.split_at(patch.len() - 12);
+        assert_eq!(&actions[..4], b"BPS1");
+        actions = &actions[4..];
+        assert_eq!(read_number(&mut actions), source.len() as u64);
+        let len = read_number(&mut actions) as usize;
+        assert_eq!(read_number(&mut actions), 0);
+        let mut target = vec![];
+        while !actions.is_empty() {
+            let action = read_number(&mut actions);
+            let count = (action >> 2) as usize + 1;
+            match action & 3 {
+                SOURCE_READ => target.extend_from_slice(&source[target.len()..target.len() + count]),
+                TARGET_READ => {
+                    target.extend_from_slice(&actions[..count]);
+                    actions = &actions[count..];
+                }
+                _ => unreachable!(),
+            }
+        }
+        assert_eq!(target.len(), len);
+        assert_eq!(footer[4..8], checksum_ieee(&target).to_le_bytes());
+        assert_eq!(footer[8..], checksum_ieee(&patch[..patch.len() - 4]).to_le_bytes());
+        target
+    }
+
+    #[test]
+    fn it_encodes_changes_and_growth() {
+        let source = (0..=255).cycle().take(0x20000).collect::<Vec<u8>>();
+        let mut target = source.clone();
+        target[0x10..0x14].copy_from_slice(b"ABCD");
+        target.resize(target.len() + 0x12345, 0xAA);
+        assert_eq!(apply(&source, &encode(&source, &target)), target);
+    }
+}
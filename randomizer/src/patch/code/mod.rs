@@ -4,18 +4,47 @@ use std::{
     fs::{self, File},
     io::prelude::*,
     path::Path,
+    str::FromStr,
 };
 
-use albw::{ExHeader, Item};
+use albw::{ExHeader, Game, Item, Region};
 
-use crate::{Result};
+use crate::{Error, Result, Settings};
 
+mod addresses;
 mod arm;
+mod bps;
 
+use addresses::Addresses;
 use arm::*;
 
 use super::Patcher;
 
+/// How the code patches are written into a LayeredFS mod folder.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CodeFormat {
+    /// An IPS patch, the most widely supported format.
+    #[default]
+    Ips,
+    /// A BPS patch, which has no limits on record size or offset.
+    Bps,
+    /// The whole decompressed code binary, already patched.
+    Bin,
+}
+
+impl FromStr for CodeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ips" => Ok(Self::Ips),
+            "bps" => Ok(Self::Bps),
+            "bin" => Ok(Self::Bin),
+            _ => Err(format!("Unknown code format '{}', expected one of: ips, bps, bin", s)),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Code {
     text: u32,
@@ -60,17 +89,52 @@ impl Code {
         self.ips.append(addr, data.into());
     }
 
-    pub fn dump<P>(self, path: P, exheader: &ExHeader) -> Result<()>
+    /// Writes the code patch to `path`, returning the extended header to go with it.
+    pub fn dump<P>(self, path: P, game: &Game, format: CodeFormat) -> Result<ExHeader>
     where
         P: AsRef<Path>,
     {
         let path = path.as_ref();
+        let mut exheader = self.exheader(game.exheader());
+        match format {
+            CodeFormat::Ips => self.ips.write(File::create(path.join("code.ips"))?)?,
+            CodeFormat::Bps => {
+                let source = game.code()?;
+                let target = self.patched(&source)?;
+                fs::write(path.join("code.bps"), bps::encode(&source, &target))?;
+            }
+            CodeFormat::Bin => {
+                let target = self.patched(&game.code()?)?;
+                fs::write(path.join("code.bin"), target)?;
+                exheader.set_code_compressed(false);
+            }
+        }
+        Ok(exheader)
+    }
+
+    fn patched(&self, code: &[u8]) -> Result<Vec<u8>> {
+        self.validate()?;
+        let mut code = code.to_vec();
+        self.apply(&mut code);
+        Ok(code)
+    }
+
+    /// The extended header with its segment sizes grown to fit any added code.
+    pub fn exheader(&self, exheader: &ExHeader) -> ExHeader {
         let mut exheader = exheader.clone();
         exheader.set_text_size(self.text - exheader.get_text_address());
         exheader.set_rodata_size(self.rodata - exheader.get_rodata_address());
-        self.ips.write(File::create(path.join("code.ips"))?)?;
-        fs::write(path.join("exheader.bin"), exheader.as_ref())?;
-        Ok(())
+        exheader
+    }
+
+    /// Checks that the patches can be applied together.
+    pub fn validate(&self) -> Result<()> {
+        self.ips.validate()
+    }
+
+    /// Applies the patch to a decompressed code binary.
+    pub fn apply(&self, code: &mut Vec<u8>) {
+        self.ips.apply(code);
     }
 }
 
@@ -117,97 +181,150 @@ impl<'a> Segment<'a> {
     }
 }
 
+/// The code patches, kept as records relative to the start of the code binary.
 #[derive(Debug)]
 pub struct Ips {
-    buf: Vec<u8>,
+    records: Vec<(u32, Box<[u8]>)>,
     offset: u32,
 }
 
 impl Ips {
     pub fn new(offset: u32) -> Self {
         Self {
-            buf: vec![],
+            records: vec![],
             offset,
         }
     }
 
     pub fn append<T>(&mut self, offset: u32, data: T)
     where
-        T: AsRef<[u8]>,
+        T: Into<Box<[u8]>>,
     {
-        let offset = offset - self.offset;
-        let data = data.as_ref();
-        self.buf.extend_from_slice(&offset.to_be_bytes()[1..4]);
-        self.buf.extend_from_slice(&data.len().to_be_bytes()[6..8]);
-        self.buf.extend(data);
+        self.records.push((offset - self.offset, data.into()));
+    }
+
+    /// Checks that no two patches write different bytes to the same address.
+    pub fn validate(&self) -> Result<()> {
+        let mut records = self.records.iter().collect::<Vec<_>>();
+        records.sort_by_key(|(offset, _)| *offset);
+        // Every byte written by the current run of overlapping records, and where that run starts
+        let mut written: Option<(u32, Vec<u8>)> = None;
+        for (start, data) in records {
+            match &mut written {
+                Some((written_start, bytes)) if *start < *written_start + bytes.len() as u32 => {
+                    let skip = (start - *written_start) as usize;
+                    let overlap = (bytes.len() - skip).min(data.len());
+                    if let Some(index) = (0..overlap).find(|index| data[*index] != bytes[skip + index]) {
+                        return Err(Error::game(format!(
+                            "Code patches overlap at {:#X} with different bytes.",
+                            start + index as u32 + self.offset
+                        )));
+                    }
+                    bytes.extend_from_slice(&data[overlap..]);
+                }
+                _ => written = Some((*start, data.to_vec())),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, code: &mut Vec<u8>) {
+        for (offset, data) in &self.records {
+            let offset = *offset as usize;
+            if code.len() < offset + data.len() {
+                code.resize(offset + data.len(), 0);
+            }
+            code[offset..offset + data.len()].copy_from_slice(data);
+        }
     }
 
+    /// Writes the patches as an IPS file, splitting records too long for its 16-bit length field.
     pub fn write<W>(self, mut writer: W) -> Result<()>
     where
         W: Write,
     {
+        self.validate()?;
         writer.write_all(b"PATCH")?;
-        writer.write_all(&self.buf)?;
+        for (offset, data) in &self.records {
+            for (i, chunk) in data.chunks(IPS_RECORD_MAX).enumerate() {
+                let offset = offset + (i * IPS_RECORD_MAX) as u32;
+                if offset > IPS_OFFSET_MAX || offset == IPS_EOF {
+                    return Err(Error::game(format!(
+                        "Code patch at {:#X} can't be stored in an IPS file, use the BPS or code.bin output instead.",
+                        offset + self.offset
+                    )));
+                }
+                writer.write_all(&offset.to_be_bytes()[1..4])?;
+                writer.write_all(&(chunk.len() as u16).to_be_bytes())?;
+                writer.write_all(chunk)?;
+            }
+        }
         writer.write_all(b"EOF")?;
         Ok(())
     }
 }
 
-pub fn create(patcher: &Patcher) -> Code {
+/// Checks that the code patches have addresses for the given release.
+pub fn check_region(region: &Region) -> Result<()> {
+    Addresses::for_region(region).map(|_| ())
+}
+
+pub fn create(patcher: &Patcher, settings: &Settings) -> Result<Code> {
+    let addr = Addresses::for_region(patcher.game.region())?;
     let mut code = Code::new(patcher.game.exheader());
 
     // Enable Y Button
-    code.text().patch(0x47B2C8, [mov(R0, 1)]);
+    code.text().patch(addr.y_button, [mov(R0, 1)]);
 
     // instant text
-    code.overwrite(0x17A430, [0xFF]);
-    rental_items(&mut code);
-    progressive_items(&mut code);
-    bracelet(&mut code);
-    ore_progress(&mut code);
-    merchant(&mut code);
+    code.overwrite(addr.text_speed, [0xFF]);
+    rental_items(&mut code, addr);
+    progressive_items(&mut code, addr, settings);
+    bracelet(&mut code, addr);
+    ore_progress(&mut code, addr);
+    merchant(&mut code, addr);
 
     // fix castle barrier?
     let master_sword_flag = code.text().define([
-        ldr(R0, EVENT_FLAG_PTR),
+        ldr(R0, addr.event_flags),
         ldr(R1, 410),
         mov(R2, 1),
         ldr(R0, (R0, 0)),
-        bl(0x4CDF40),
+        bl(addr.set_event_flag),
         mov(R0, 1),
         pop([R4, R5, R6, PC]),
     ]);
-    code.patch(0x344E50, [b(master_sword_flag)]);
+    code.patch(addr.master_sword_flag, [b(master_sword_flag)]);
 
     // don't lose Bow of Light on defeat
-    code.patch(0x502DD8, [mov(R0, R0)]);
+    code.patch(addr.bow_of_light_defeat, [mov(R0, R0)]);
     // blacksmith
     let get_sword_flag1 = code.text().define([
         push([LR]),
-        ldr(R0, EVENT_FLAG_PTR),
+        ldr(R0, addr.event_flags),
         ldr(R0, (R0, 0)),
         ldr(R1, 0x375),
-        bl(0x584A80),
+        bl(addr.get_event_value),
         add(R0, R0, 3),
         pop([PC]),
     ]);
     let get_sword_flag2 = code.text().define([
         push([LR]),
-        ldr(R0, 0x70C8E0),
+        ldr(R0, addr.player_data),
         ldr(R0, (R0, 0)),
         mov(R1, 0xCE),
         mov(R2, 3),
-        bl(0x5822A0),
+        bl(addr.get_player_flag),
         add(R0, R0, 4),
         pop([PC]),
     ]);
-    code.patch(0x243DE8, [bl(get_sword_flag1)]);
-    code.patch(0x30E160, [bl(get_sword_flag2)]);
-    let actor_names = actor_names(&mut code);
-    let item_names = item_names(&mut code);
+    code.patch(addr.blacksmith[0], [bl(get_sword_flag1)]);
+    code.patch(addr.blacksmith[1], [bl(get_sword_flag2)]);
+    let actor_names = actor_names(&mut code, addr);
+    let item_names = item_names(&mut code, addr);
     let overwrite_rentals = code.text;
     let mut actor_offset = 0;
-    let mut name_offset = 0x714608;
+    let mut name_offset = addr.rental_names;
     for rental in patcher.rentals.iter() {
         let actor = actor_names
             .get(rental)
@@ -220,102 +337,98 @@ pub fn create(patcher: &Patcher) -> Code {
             add(PC, PC, 0), // bad hack
         ]);
         actor_offset += 8;
-        let name = item_names.get(rental).copied().unwrap_or(0x6F9B1A);
+        let name = item_names.get(rental).copied().unwrap_or(addr.default_item_name);
         code.overwrite(name_offset, name.to_le_bytes());
         name_offset += 4;
     }
-    code.text().define([b(0x5D68F4)]);
-    code.patch(0x5D688C, [b(overwrite_rentals)]);
+    code.text().define([b(addr.rental_actors_end)]);
+    code.patch(addr.rental_actors, [b(overwrite_rentals)]);
     let rentals = patcher
         .rentals
         .iter()
         .map(|item| *item as u8)
         .collect::<Vec<_>>();
-    code.overwrite(0x6A0348, rentals);
-    let sold_out = 0x5D6B84u32;
+    code.overwrite(addr.rental_items, rentals);
     let merchant_left = patcher.merchant[0];
-    let merchant_left_actor = code.rodata().declare(VTABLE_STRING.to_le_bytes());
+    let merchant_left_actor = code.rodata().declare(addr.vtable_string.to_le_bytes());
     code.rodata()
         .declare(actor_names.get(&merchant_left).unwrap().to_le_bytes());
-    code.rodata().declare(VTABLE_STRING.to_le_bytes());
-    code.rodata().declare(sold_out.to_le_bytes());
-    code.overwrite(0x707DD4, merchant_left_actor.to_le_bytes());
-    code.overwrite(0x6A03E0, [merchant_left as u8]);
+    code.rodata().declare(addr.vtable_string.to_le_bytes());
+    code.rodata().declare(addr.sold_out.to_le_bytes());
+    code.overwrite(addr.merchant_actors[0], merchant_left_actor.to_le_bytes());
+    code.overwrite(addr.merchant_items[0], [merchant_left as u8]);
     let merchant_right = patcher.merchant[2];
-    let merchant_right_actor = code.rodata().declare(VTABLE_STRING.to_le_bytes());
+    let merchant_right_actor = code.rodata().declare(addr.vtable_string.to_le_bytes());
     code.rodata()
         .declare(actor_names.get(&merchant_right).unwrap().to_le_bytes());
-    code.rodata().declare(VTABLE_STRING.to_le_bytes());
-    code.rodata().declare(sold_out.to_le_bytes());
-    code.overwrite(0x707DE0, merchant_right_actor.to_le_bytes());
-    code.overwrite(0x6A03E8, [merchant_right as u8]);
+    code.rodata().declare(addr.vtable_string.to_le_bytes());
+    code.rodata().declare(addr.sold_out.to_le_bytes());
+    code.overwrite(addr.merchant_actors[1], merchant_right_actor.to_le_bytes());
+    code.overwrite(addr.merchant_items[1], [merchant_right as u8]);
     // Hearts
-    code.patch(0x33497C, [ldr(R1, (R4, 0x2E)), mov(R0, R0)]);
+    code.patch(addr.heart_flag, [ldr(R1, (R4, 0x2E)), mov(R0, R0)]);
     // Keys
-    code.patch(0x192E58, [ldr(R1, (R4, 0x2E))]);
+    code.patch(addr.key_flag, [ldr(R1, (R4, 0x2E))]);
     // Premium milk
     let premium_milk = code.text().define([
-        ldr(R0, EVENT_FLAG_PTR),
+        ldr(R0, addr.event_flags),
         mov(R2, 1),
         ldr(R1, 0x395),
         ldr(R0, (R0, 0)),
-        bl(SET_EVENT_FLAG_FN),
-        b(0x344F00),
+        bl(addr.set_event_flag),
+        b(addr.get_item_end),
     ]);
-    code.patch(0x3455C4, [b(premium_milk)]);
+    code.patch(addr.premium_milk, [b(premium_milk)]);
     // Do not add message bottle or premium milk to inventory
-    code.patch(0x345580, [mov(R0, 0xFF)]);
-    code.patch(0x3455C0, [mov(R0, 0xFF)]);
-    code
+    code.patch(addr.no_inventory[0], [mov(R0, 0xFF)]);
+    code.patch(addr.no_inventory[1], [mov(R0, 0xFF)]);
+    Ok(code)
 }
 
-fn merchant(code: &mut Code) {
+fn merchant(code: &mut Code, addr: &Addresses) {
     let get_merchant_event_flag = code.text().define([
-        ldr(R0, EVENT_FLAG_PTR),
+        ldr(R0, addr.event_flags),
         ldr(R0, (R0, 0)),
         ldr(R1, 0x143),
-        b(GET_EVENT_FLAG_FN),
+        b(addr.get_event_flag),
     ]);
-    code.patch(0x19487C, [bl(get_merchant_event_flag)]);
+    code.patch(addr.merchant_flag, [bl(get_merchant_event_flag)]);
 }
 
-fn rental_items(code: &mut Code) {
-    let map_rental_item = 0x194BFC;
+fn rental_items(code: &mut Code, addr: &Addresses) {
     let flag_offset = 0xF0;
     let getter = code.text().define([
         push([LR]),
-        ldr(R0, 0x70C8E0),
+        ldr(R0, addr.player_data),
         ldr(R0, (R0, 0)),
         add(R1, R1, flag_offset),
         mov(R2, 3),
-        bl(0x5822A0),
+        bl(addr.get_player_flag),
         cmp(R0, 1),
         mov(R0, 2).eq(),
         pop([PC]),
     ]);
-    code.patch(0x194728, [bl(getter)]);
-    code.patch(0x311CE4, [bl(getter)]);
-    code.patch(0x311EAC, [bl(getter)]);
-    code.patch(0x31261C, [bl(getter)]);
-    code.patch(0x312660, [bl(getter)]);
+    for site in addr.rental_getters {
+        code.patch(site, [bl(getter)]);
+    }
     let setter = code.text().define([
         ldrb(R0, (R4, 0x9D0)),
-        bl(map_rental_item),
+        bl(addr.map_rental_item),
         mov(R6, R1),
         mov(R1, R0),
         add(R1, R1, flag_offset),
-        ldr(R0, 0x70C8E0),
+        ldr(R0, addr.player_data),
         ldr(R0, (R0, 0)),
         mov(R2, 3),
         mov(R3, 1),
-        bl(0x4AD9E8),
-        b(0x652E70),
+        bl(addr.set_player_flag),
+        b(addr.rental_setter_end),
     ]);
-    code.patch(0x652E34, [b(setter).eq()]);
+    code.patch(addr.rental_setter, [b(setter).eq()]);
 }
 
-fn progressive_items(code: &mut Code) {
-    let return_label = 0x2922C4;
+fn progressive_items(code: &mut Code, addr: &Addresses, settings: &Settings) {
+    let return_label = addr.progressive_items_end;
     /*let first_sword = code.text().define([
         ldr(R0, (R0, 0x4C4)),
         cmp(R0, 0),
@@ -403,61 +516,85 @@ fn progressive_items(code: &mut Code) {
         mov(R5, 0x59).ne(),
         b(return_label),
     ]);
-    code.patch(0x2922A0, [b(progressive_net)]);
+    let mut progressive_chain = progressive_net;
+    if settings.logic.nice_items {
+        // The Lamp and Net's slots sit right after Ravio's items in the order the item menu shows them
+        let slots = [
+            (0x11, 0x440), // Bow
+            (0x0F, 0x444), // Boomerang
+            (0x0E, 0x448), // Hookshot
+            (0x0C, 0x44C), // Bombs
+            (0x0D, 0x450), // Fire Rod
+            (0x09, 0x454), // Ice Rod
+            (0x10, 0x458), // Hammer
+            (0x0A, 0x45C), // Sand Rod
+            (0x0B, 0x460), // Tornado Rod
+        ];
+        for (item, slot) in slots {
+            progressive_chain = code.text().define([
+                cmp(R5, item),
+                b(progressive_chain).ne(),
+                ldr(R0, (R0, slot)),
+                cmp(R0, 0),
+                mov(R5, item).eq(),
+                mov(R5, item + 0x44).ne(),
+                b(return_label),
+            ]);
+        }
+    }
+    code.patch(addr.progressive_items, [b(progressive_chain)]);
 }
 
-fn bracelet(code: &mut Code) {
-    let item_set_value = 0x255494;
+fn bracelet(code: &mut Code, addr: &Addresses) {
     let add_ring_hekiga = code.text().define([
         add(R0, R4, 0x400),
         mov(R2, 3),
         mov(R1, 0x17),
         add(R0, R0, 0xC),
-        bl(item_set_value),
-        b(0x344F00),
+        bl(addr.set_item_value),
+        b(addr.get_item_end),
     ]);
-    code.overwrite(0x3448F4, add_ring_hekiga.to_le_bytes());
-    let get_item_value = 0x55696C;
+    code.overwrite(addr.ring_hekiga, add_ring_hekiga.to_le_bytes());
     let is_ring_hekiga = code.text().define([
         push([LR]),
-        ldr(R0, 0x70FB60),
+        ldr(R0, addr.item_data),
         ldr(R0, (R0, 0)),
         mov(R1, 0x17),
-        bl(get_item_value),
+        bl(addr.get_item_value),
         cmp(R0, 3),
         mov(R0, 1).eq(),
         mov(R0, 0).ne(),
         pop([PC]),
     ]);
-    code.patch(0x1DCA8C, [bl(is_ring_hekiga)]);
-    code.patch(0x4266D0, [bl(is_ring_hekiga)]);
-    code.patch(0x52E654, [bl(is_ring_hekiga)]);
+    for site in addr.is_ring_hekiga {
+        code.patch(site, [bl(is_ring_hekiga)]);
+    }
 }
 
-fn ore_progress(code: &mut Code) {
+fn ore_progress(code: &mut Code, addr: &Addresses) {
     let get_sword_fake = code.text().define([
         push([R4, LR]),
         mov(R4, 1),
-        ldr(R0, 0x70C8E0),
+        ldr(R0, addr.player_data),
         ldr(R0, (R0, 0)),
         add(R0, R0, 0x400),
         add(R0, R0, 0x88),
         mov(R1, 0xCE),
-        bl(0x52A05C),
+        bl(addr.get_player_value),
         add(R4, R4, R0),
-        ldr(R0, EVENT_FLAG_PTR),
+        ldr(R0, addr.event_flags),
         ldr(R0, (R0, 0)),
         ldr(R1, 0x375),
-        bl(0x584A80),
+        bl(addr.get_event_value),
         add(R4, R4, R0),
         mov(R0, R4),
         pop([R4, PC]),
     ]);
-    code.patch(0x4637B8, [bl(get_sword_fake)]);
+    code.patch(addr.ore_progress, [bl(get_sword_fake)]);
 }
 
-fn actor_names(code: &mut Code) -> HashMap<Item, u32> {
-    let mut map = array::IntoIter::new(ACTOR_NAME_OFFSETS).collect::<HashMap<_, _>>();
+fn actor_names(code: &mut Code, addr: &Addresses) -> HashMap<Item, u32> {
+    let mut map = addr.actor_names.iter().copied().collect::<HashMap<_, _>>();
     map.extend(array::IntoIter::new(ACTOR_NAMES).map(|(item, name)| {
         let name = format!("{}\0", name);
         (item, code.rodata().declare(name.as_bytes()))
@@ -465,8 +602,8 @@ fn actor_names(code: &mut Code) -> HashMap<Item, u32> {
     map
 }
 
-fn item_names(code: &mut Code) -> HashMap<Item, u32> {
-    let mut map = array::IntoIter::new(ITEM_NAME_OFFSETS).collect::<HashMap<_, _>>();
+fn item_names(code: &mut Code, addr: &Addresses) -> HashMap<Item, u32> {
+    let mut map = addr.item_names.iter().copied().collect::<HashMap<_, _>>();
     map.extend(array::IntoIter::new(ITEM_NAMES).map(|(item, name)| {
         let name = format!("item_name_{}\0", name);
         (item, code.rodata().declare(name.as_bytes()))
@@ -474,37 +611,10 @@ fn item_names(code: &mut Code) -> HashMap<Item, u32> {
     map
 }
 
-const ACTOR_NAME_OFFSETS: [(Item, u32); 29] = [
-    (Item::ItemStoneBeauty, 0x5D2060),
-    (Item::RupeeR, 0x5D639C),
-    (Item::RupeeG, 0x5D639C),
-    (Item::RupeeB, 0x5D639C),
-    (Item::RupeePurple, 0x5D639C),
-    (Item::RupeeSilver, 0x5D63A4),
-    (Item::KeySmall, 0x5D6580),
-    (Item::ItemIceRod, 0x5D6AFC),
-    (Item::ItemSandRod, 0x5D6B08),
-    (Item::ItemTornadeRod, 0x5D6B18),
-    (Item::ItemBomb, 0x5D6B28),
-    (Item::ItemFireRod, 0x5D6B30),
-    (Item::ItemHookShot, 0x5D6B40),
-    (Item::ItemBoomerang, 0x5D6B50),
-    (Item::ItemHammer, 0x5D6B60),
-    (Item::ItemBow, 0x5D6B6C),
-    (Item::ItemShield, 0x5D6B78),
-    (Item::ItemBottle, 0x5D7048),
-    (Item::HintGlasses, 0x5D70AC),
-    (Item::RupeeGold, 0x5D7144),
-    (Item::ItemSwordLv2, 0x5D7178),
-    (Item::LiverPurple, 0x5D762C),
-    (Item::LiverYellow, 0x5D7640),
-    (Item::LiverBlue, 0x5D7654),
-    (Item::MessageBottle, 0x5D76A0),
-    (Item::Pouch, 0x5D7734),
-    (Item::ItemBowLight, 0x5D776C),
-    (Item::HeartContainer, 0x5D7B7C),
-    (Item::HeartPiece, 0x5D7B94),
-];
+/// The label of an item's name in the message files, without the `item_name_` prefix, for items the code names.
+pub(super) fn item_label(item: Item) -> Option<&'static str> {
+    ITEM_NAMES.iter().find(|(named, _)| *named == item).map(|(_, label)| *label)
+}
 
 const ACTOR_NAMES: [(Item, &str); 29] = [
     (Item::KeyBoss, "KeyBoss"),
@@ -538,32 +648,6 @@ const ACTOR_NAMES: [(Item, &str); 29] = [
     (Item::MilkMatured, "GtEvBottleMedicine"), // Red Milk lol
 ];
 
-const ITEM_NAME_OFFSETS: [(Item, u32); 23] = [
-    (Item::ItemBomb, 0x6F9A9A),
-    (Item::ItemBombLv2, 0x6F9A9A),
-    (Item::ItemSandRod, 0x6F9AD0),
-    (Item::ItemSandRodLv2, 0x6F9AD0),
-    (Item::ItemIceRod, 0x6F9AE2),
-    (Item::ItemIceRodLv2, 0x6F9AE2),
-    (Item::ItemTornadeRod, 0x6F9AF3),
-    (Item::ItemTornadeRodLv2, 0x6F9AF3),
-    (Item::ItemFireRod, 0x6F9B08),
-    (Item::ItemFireRodLv2, 0x6F9B08),
-    (Item::LiverPurple, 0x6F9B55),
-    (Item::ItemBottle, 0x6F9B6C),
-    (Item::LiverBlue, 0x6F9B94),
-    (Item::ItemBoomerang, 0x6F9BA9),
-    (Item::ItemBoomerangLv2, 0x6F9BA9),
-    (Item::ItemHammer, 0x6F9CCC),
-    (Item::ItemHammerLv2, 0x6F9CCC),
-    (Item::ItemHookShot, 0x6F9CDD),
-    (Item::ItemHookShotLv2, 0x6F9CDD),
-    (Item::ItemBow, 0x6F9D08),
-    (Item::ItemBowLv2, 0x6F9D08),
-    (Item::LiverYellow, 0x6F9D2F),
-    (Item::ItemStoneBeauty, 0x6F9D56),
-];
-
 const ITEM_NAMES: [(Item, &str); 25] = [
     (Item::HeartContainer, "heartcontioner"),
     (Item::HeartPiece, "heartpiece"),
@@ -592,7 +676,57 @@ const ITEM_NAMES: [(Item, &str); 25] = [
     (Item::OreRed, "ore"),
 ];
 
-const SET_EVENT_FLAG_FN: u32 = 0x4CDF40;
-const GET_EVENT_FLAG_FN: u32 = 0x584B94;
-const VTABLE_STRING: u32 = 0x6F5988;
-const EVENT_FLAG_PTR: u32 = 0x70B728;
+const IPS_RECORD_MAX: usize = 0xFFFF;
+const IPS_OFFSET_MAX: u32 = 0xFFFFFF;
+/// An offset that reads as the "EOF" marker, so no record can start there.
+const IPS_EOF: u32 = 0x454F46;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_splits_long_ips_records() -> Result<()> {
+        let mut ips = Ips::new(0x100000);
+        ips.append(0x100010, vec![0xAA; 0x10001]);
+        let mut buf = vec![];
+        ips.write(&mut buf)?;
+        assert_eq!(&buf[5..10], &[0, 0, 0x10, 0xFF, 0xFF]);
+        assert_eq!(&buf[0x10009..0x1000E], &[0x01, 0x00, 0x0F, 0, 2]);
+        assert_eq!(&buf[buf.len() - 3..], b"EOF");
+        Ok(())
+    }
+
+    #[test]
+    fn it_rejects_unencodable_ips_offsets() {
+        for offset in [IPS_EOF, 0x1000000] {
+            let mut ips = Ips::new(0);
+            ips.append(offset, [0]);
+            assert!(ips.write(vec![]).is_err());
+        }
+    }
+
+    #[test]
+    fn it_detects_conflicting_writes() {
+        let mut ips = Ips::new(0);
+        ips.append(0x10, [1, 2, 3, 4]);
+        ips.append(0x12, [3, 4, 5]);
+        assert!(ips.validate().is_ok());
+        ips.append(0x8, [0; 0x20]);
+        assert!(ips.validate().is_err());
+    }
+
+    #[test]
+    fn it_detects_conflicts_with_earlier_records() {
+        let mut ips = Ips::new(0);
+        ips.append(0, [1; 10]);
+        ips.append(5, [1; 15]);
+        ips.append(8, [1; 4]);
+        assert!(ips.validate().is_ok());
+        let mut ips = Ips::new(0);
+        ips.append(0, [1; 10]);
+        ips.append(5, [1; 15]);
+        ips.append(8, [2, 1, 1, 1]);
+        assert!(ips.validate().is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+
+use albw::{course, scene::Stage, Scene};
+use log::{info, warn};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use super::Patcher;
+use crate::{Error, Result, Seed};
+
+/// Enemy actor IDs that can stand in for each other, grouped by the terrain they need.
+///
+/// Only enemies that are never needed for logic are listed, so switch-holding enemies and Wallmasters stay put.
+/// Flying, water and wall-merge-safe categories are left out until the actor IDs of those enemies are known.
+const CATEGORIES: [&[i16]; 1] = [
+    // Ground
+    &[
+        33,  // Stalfos
+        38,  // Tektite
+        73,  // Green Soldier (Sword)
+        76,  // Blue Soldier
+        77,  // Green Soldier (Dagger)
+        132, // Gibdo
+        243, // Popo
+    ],
+];
+
+/// Enemies that stay as they are even though their kind is shuffled, by course, stage and unq.
+///
+/// These are the soldiers that the scene patches turn on and expect to find there.
+const PROTECTED: &[(course::Id, u16, &[u16])] = &[
+    (course::Id::FieldLight, 11, &[85, 86, 87]), // Outside Sanctuary
+    (course::Id::FieldLight, 12, &[93, 94]), // Graveyard
+    (course::Id::FieldLight, 18, &[263, 308, 536]), // Hyrule Castle
+];
+
+/// An enemy placed in a scene.
+#[derive(Clone, Copy, Debug)]
+struct Enemy {
+    course: course::Id,
+    stage: u16,
+    unq: u16,
+    id: i16,
+}
+
+/// Where an enemy's actors can be copied from.
+#[derive(Debug)]
+struct Source {
+    course: course::Id,
+    stage: u16,
+    /// Actors found in every scene the enemy appears in, and so the ones it may need.
+    actors: BTreeSet<u32>,
+    scenes: usize,
+}
+
+/// Shuffles the enemies of every scene among each other, keeping every enemy within its category.
+///
+/// Actor archives only keep hashes of their paths, so an enemy's actors are taken to be the ones shared by every scene
+/// it appears in, apart from those that every scene has. An enemy found in only one scene can't be told apart from
+/// the rest of that scene, so it stays where it is. So do enemies that may matter to logic: those listed in
+/// [`PROTECTED`], those holding a shuffled item, and those that set flags or are linked to other objects, such as
+/// the doors of rooms that open once every enemy is defeated.
+pub(super) fn shuffle(patcher: &mut Patcher, seed: Seed) -> Result<()> {
+    info!("Shuffling Enemies...");
+    let mut rng = StdRng::seed_from_u64(seed as u64);
+
+    let mut enemies = Vec::new();
+    let mut sources = BTreeMap::<i16, Source>::new();
+    let mut common: Option<BTreeSet<u32>> = None;
+    for (course, stage, scene) in scenes(patcher) {
+        let scene = scene?;
+        let actors = scene.actors().hashes()?.into_iter().collect::<BTreeSet<_>>();
+        let mut ids = BTreeSet::new();
+        let layout = scene.stage().get();
+        for obj in layout.objs() {
+            if CATEGORIES.iter().any(|category| category.contains(&obj.id()))
+                && !is_protected(patcher, course, stage, layout, obj.unq())
+            {
+                enemies.push(Enemy { course, stage, unq: obj.unq(), id: obj.id() });
+                ids.insert(obj.id());
+            }
+        }
+        for id in ids {
+            sources.entry(id)
+                .and_modify(|source| {
+                    source.actors.retain(|hash| actors.contains(hash));
+                    source.scenes += 1;
+                })
+                .or_insert_with(|| Source { course, stage, actors: actors.clone(), scenes: 1 });
+        }
+        common = Some(match common {
+            Some(common) => common.intersection(&actors).copied().collect(),
+            None => actors,
+        });
+    }
+    let common = common.unwrap_or_default();
+    sources.retain(|_, source| source.scenes > 1);
+    for source in sources.values_mut() {
+        source.actors.retain(|hash| !common.contains(hash));
+    }
+    enemies.retain(|enemy| sources.contains_key(&enemy.id));
+
+    let mut swaps = Vec::new();
+    for category in CATEGORIES {
+        let placed = enemies.iter()
+            .filter(|enemy| category.contains(&enemy.id))
+            .collect::<Vec<_>>();
+        let mut ids = placed.iter().map(|enemy| enemy.id).collect::<Vec<_>>();
+        ids.shuffle(&mut rng);
+        swaps.extend(placed.into_iter().zip(ids).filter(|(enemy, id)| enemy.id != *id).map(|(enemy, id)| (*enemy, id)));
+    }
+
+    let mut loaded = BTreeMap::new();
+    for (enemy, id) in swaps {
+        let source = &sources[&id];
+        let needed = {
+            let actors = patcher.scene(enemy.course, enemy.stage)?.actors().hashes()?;
+            source.actors.iter().copied().filter(|hash| !actors.contains(hash)).collect::<Vec<_>>()
+        };
+        if !needed.is_empty() {
+            let from = match loaded.entry((source.course, source.stage)) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(patcher.game.course(source.course).scene(source.stage)?),
+            };
+            patcher.scene(enemy.course, enemy.stage)?.actors_mut().copy_from(from.actors(), needed)?;
+        }
+        patcher.scene(enemy.course, enemy.stage)?
+            .stage_mut()
+            .get_mut()
+            .get_mut(enemy.unq)
+            .ok_or_else(|| {
+                Error::game(format!("{}{} [{}] not found", enemy.course.as_str(), enemy.stage + 1, enemy.unq))
+            })?
+            .set_id(id);
+    }
+    Ok(())
+}
+
+/// Whether the enemy `unq` of a stage has to stay as it is.
+fn is_protected(patcher: &Patcher, course: course::Id, stage: u16, layout: &Stage, unq: u16) -> bool {
+    if PROTECTED.iter().any(|(protected, number, unqs)| *protected == course && *number == stage + 1 && unqs.contains(&unq))
+        || patcher.placed.contains(&(course, stage, unq))
+    {
+        return true;
+    }
+    let enemy = match layout.objs().iter().find(|obj| obj.unq() == unq) {
+        Some(enemy) => enemy,
+        None => return false,
+    };
+    let arg = enemy.arg();
+    !enemy.lnk().is_empty()
+        || arg.4 != 0
+        || arg.5 != 0
+        || layout.objs().iter().chain(layout.system()).any(|obj| obj.lnk().iter().any(|lnk| lnk.0 == unq))
+}
+
+/// Reads every scene of every course but the demo ones, straight from the game.
+///
+/// A stage without an actor archive is skipped on its own, only a missing stage layout marks the end of a course.
+fn scenes(patcher: &Patcher) -> impl Iterator<Item = (course::Id, u16, Result<Scene>)> + '_ {
+    course::Id::iter().filter(|course| *course != course::Id::Demo).flat_map(move |course| {
+        // Stages are numbered consecutively, the first missing one marks the end of the course
+        (0..)
+            .map(move |stage| (stage, patcher.game.course(course).scene(stage)))
+            .take_while(move |(stage, scene)| !(not_found(scene) && not_found(&patcher.game.course(course).stage(*stage))))
+            .filter(move |(stage, scene)| {
+                if not_found(scene) {
+                    warn!("Not shuffling the enemies of {}{}, its actors weren't found.", course.as_str(), stage + 1);
+                }
+                !not_found(scene)
+            })
+            .map(move |(stage, scene)| (course, stage, scene.map_err(Into::into)))
+    })
+}
+
+fn not_found<T>(result: &albw::Result<T>) -> bool {
+    matches!(result, Err(err) if matches!(err.kind(), albw::ErrorKind::NotFound))
+}
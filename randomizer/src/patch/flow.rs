@@ -7,9 +7,8 @@ macro_rules! apply {
     ($patcher:expr, $($course:ident/$name:ident {
         $([$index:literal $($kind:ident)?] $op:tt $rhs:tt,)*
     },)+) => {
-        $({
-            let mut flow = $patcher
-                .flow(course!($course))?;
+        $(for language in $patcher.languages(course!($course))? {
+            let mut flow = language.flow_mut();
             let mut flow = flow
                 .get_mut(stringify!($name))
                 .ok_or_else(|| $crate::Error::game("File not found."))??;
@@ -0,0 +1,204 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap},
+};
+
+use albw::{course, flow::Step, Item, Language};
+use log::warn;
+
+use super::{code::item_label, Patcher, Text};
+use crate::{item_to_str, ItemExt, Result};
+
+/// Rewrites item names in the messages shown by shuffled shop and event checks.
+///
+/// Each event's text, Mother Maiamai's offers included, is assumed to live in the message file sharing its flow's
+/// name, and only the messages the flow's text steps show are rewritten. Names come from each language's own item
+/// name messages where the item has one, falling back to the English name otherwise. Files or flows that can't be
+/// found are left vanilla rather than failing the patch.
+pub fn apply(patcher: &mut Patcher) -> Result<()> {
+    let mut courses = BTreeMap::<Option<course::Id>, BTreeMap<String, Vec<(Item, Item)>>>::new();
+    for Text {
+        course,
+        file,
+        vanilla,
+        item,
+    } in patcher.texts.iter().cloned()
+    {
+        let item = item.normalize();
+        if vanilla.normalize() == item || in_game_name(vanilla).is_none() {
+            continue;
+        }
+        courses
+            .entry(course)
+            .or_default()
+            .entry(file)
+            .or_default()
+            .push((vanilla, item));
+    }
+
+    for (course, files) in courses {
+        for language in patcher.languages(course)? {
+            let local = local_names(language)?;
+            for (file, items) in &files {
+                rewrite(language, file, &local_pairs(&local, items))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Rewrites the names in the messages shown by the flow sharing the file's name.
+fn rewrite(language: &mut Language, file: &str, names: &[(String, String)]) -> Result<()> {
+    let mut msbt = match language.msbt(file) {
+        Ok(msbt) => msbt,
+        Err(err) => {
+            warn!("Could not rewrite text in {}: {}", file, err.into_inner());
+            return Ok(());
+        }
+    };
+    let shown = match language.flow().get(file) {
+        Some(flow) => flow?
+            .get()
+            .steps()
+            .iter()
+            .filter_map(|step| match step {
+                Ok(Step::Text { message, .. }) => Some(Ok(message as u32)),
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            })
+            .collect::<albw::Result<BTreeSet<_>>>()?,
+        None => {
+            warn!("Could not rewrite text in {}: no flow shows it", file);
+            return Ok(());
+        }
+    };
+    let labels = msbt
+        .get()
+        .labels()
+        .filter(|(_, index)| shown.contains(index))
+        .map(|(label, _)| label.to_string())
+        .collect::<Vec<_>>();
+    let names = names
+        .iter()
+        .map(|(from, to)| (from.as_str(), to.as_str()))
+        .collect::<Vec<_>>();
+    for label in labels {
+        if let Some(message) = msbt.get_mut().get_mut(&label) {
+            message.map_text(|text| replace_names(text, &names));
+        }
+    }
+    language.update(msbt)?;
+    Ok(())
+}
+
+/// The item names in a language's own message files, by label without the `item_name_` prefix.
+fn local_names(language: &Language) -> Result<HashMap<String, String>> {
+    let mut names = HashMap::new();
+    for msbt in language.messages()? {
+        for (label, _) in msbt.labels() {
+            if let Some(name) = label.strip_prefix("item_name_") {
+                if let Some(message) = msbt.get(label) {
+                    names.insert(name.to_string(), message.text());
+                }
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Pairs each vanilla item's name with its replacement's, in the language the local names are from.
+fn local_pairs(local: &HashMap<String, String>, items: &[(Item, Item)]) -> Vec<(String, String)> {
+    let name = |item: Item| {
+        item_label(item)
+            .and_then(|label| local.get(label).cloned())
+            .or_else(|| in_game_name(item).map(str::to_string))
+    };
+    items
+        .iter()
+        .filter_map(|&(vanilla, item)| {
+            let to = name(item).unwrap_or_else(|| item_to_str(&item).to_string());
+            Some((name(vanilla)?, to))
+        })
+        .collect()
+}
+
+/// Replaces every name at once, so that two items trading places don't both end up with the same name.
+///
+/// Longer names are tried first, so that "Bow" doesn't match the start of "Bow of Light".
+fn replace_names(text: &str, names: &[(&str, &str)]) -> String {
+    let mut names = names.to_vec();
+    names.sort_by_key(|(from, _)| Reverse(from.len()));
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    'outer: while !rest.is_empty() {
+        for (from, to) in &names {
+            if let Some(after) = rest.strip_prefix(from) {
+                result.push_str(to);
+                rest = after;
+                continue 'outer;
+            }
+        }
+        let ch = rest.chars().next().unwrap();
+        result.push(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+    result
+}
+
+/// The name the game's own text uses for an item, where it differs from the spoiler log.
+fn in_game_name(item: Item) -> Option<&'static str> {
+    Some(match item {
+        Item::ItemSwordLv1 | Item::ItemSwordLv3 | Item::ItemSwordLv4 => return None,
+        Item::RingHekiga | Item::RingRental => "Ravio's Bracelet",
+        Item::ItemMizukaki => "Zora's Flippers",
+        Item::ItemInsectNet => "Bug-Catching Net",
+        item => item_to_str(&item.normalize()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_swaps_names_at_once() {
+        let names = [("Ice Rod", "Bow"), ("Bow", "Ice Rod")];
+        assert_eq!(
+            replace_names("Ice Rod: 50 Rupees. Bow: 50 Rupees.", &names),
+            "Bow: 50 Rupees. Ice Rod: 50 Rupees."
+        );
+    }
+
+    #[test]
+    fn it_prefers_longer_names() {
+        let names = [("Bow", "Hammer"), ("Bow of Light", "Lamp")];
+        assert_eq!(
+            replace_names("Bow of Light, not a Bow.", &names),
+            "Lamp, not a Hammer."
+        );
+    }
+
+    #[test]
+    fn it_uses_local_names() {
+        let local = HashMap::from([("net".to_string(), "Filet".to_string())]);
+        assert_eq!(
+            local_pairs(
+                &local,
+                &[
+                    (Item::ItemInsectNet, Item::ItemIceRod),
+                    (Item::ItemBow, Item::ItemInsectNet)
+                ]
+            ),
+            [
+                ("Filet".to_string(), "Ice Rod".to_string()),
+                ("Bow".to_string(), "Filet".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn it_uses_in_game_names() {
+        assert_eq!(in_game_name(Item::ItemInsectNet), Some("Bug-Catching Net"));
+        assert_eq!(in_game_name(Item::RingRental), Some("Ravio's Bracelet"));
+    }
+}
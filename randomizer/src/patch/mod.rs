@@ -1,20 +1,21 @@
-use std::{array, collections::HashMap, fs, iter, path::Path};
-use std::io::{Read, stdin, stdout, Write};
+use std::{array, collections::{BTreeMap, HashMap, HashSet}, fs, iter, mem, path::{Path, PathBuf}, slice, str::FromStr};
 
-use albw::{course, demo::Timed, flow::FlowMut, Demo, File, Game, IntoBytes, Item, Language, Scene};
-use fs_extra::dir::CopyOptions;
-use log::{error, info};
+use albw::{course, demo::Timed, Demo, File, Game, ImageFormat, IntoBytes, Item, Language, Scene};
+use log::{info, warn};
 use serde::Serialize;
-use tempfile::tempdir;
 use try_insert_ext::*;
+use albw::flow::{Action, Step};
 use albw::scene::Arg;
 
-use crate::{Error, Result, Settings};
+use crate::{Error, ErrorKind, Result, Seed, Settings};
 
+pub use self::code::CodeFormat;
 use self::code::Code;
 
 mod code;
+mod enemies;
 mod flow;
+mod messages;
 mod scenes;
 
 #[derive(Debug)]
@@ -23,17 +24,33 @@ pub struct Patcher {
     boot: Language,
     rentals: [Item; 9],
     merchant: [Item; 3],
+    texts: Vec<Text>,
+    languages: Vec<&'static str>,
+    /// How many shuffled events use each action command to give their item.
+    give_items: BTreeMap<u16, usize>,
+    /// Mother Maiamai's offers, applied once every event is known.
+    upgrades: Vec<(Item, Item)>,
+    /// Scene objects holding a shuffled item, which enemy shuffle leaves where they are.
+    placed: HashSet<(course::Id, u16, u16)>,
     courses: HashMap<course::Id, Course>,
 }
 
 impl Patcher {
-    pub fn new(game: Game) -> Result<Self> {
+    /// Prepares to patch the game, writing only the languages chosen in the settings.
+    pub fn new(game: Game, settings: &Settings) -> Result<Self> {
+        code::check_region(game.region())?;
+        let languages = select_languages(game.region().languages(), &settings.options.languages)?;
         let boot = game.boot()?;
         Ok(Self {
             game,
             boot,
             rentals: [Item::KeySmall; 9],
             merchant: [Item::KeySmall; 3],
+            texts: vec![],
+            languages,
+            give_items: Default::default(),
+            upgrades: vec![],
+            placed: Default::default(),
             courses: Default::default(),
         })
     }
@@ -41,58 +58,59 @@ impl Patcher {
     fn course(&mut self, course: course::Id) -> Result<&mut Course> {
         let Self {
             game,
+            languages,
             ref mut courses,
             ..
         } = self;
         courses
             .entry(course)
-            .or_try_insert_with(|| {
-                game.course(course).language().map(|load| Course {
-                    language: load,
-                    scenes: Default::default(),
-                })
-            })
-            .map_err(Into::into)
+            .or_try_insert_with(|| Course::load(game, languages, course))
     }
 
     fn scene(&mut self, course: course::Id, stage: u16) -> Result<&mut Scene> {
         let Self {
             game,
+            languages,
             ref mut courses,
             ..
         } = self;
         courses
             .entry(course)
-            .or_try_insert_with(|| {
-                game.course(course).language().map(|load| Course {
-                    language: load,
-                    scenes: Default::default(),
-                })
-            })?
+            .or_try_insert_with(|| Course::load(game, languages, course))?
             .scenes
             .entry(stage)
             .or_try_insert_with(|| game.course(course).scene(stage))
             .map_err(Into::into)
     }
 
-    fn language<C>(&mut self, course: C) -> Result<&mut Language>
+    /// Shuffles the enemies across every scene.
+    pub fn shuffle_enemies(&mut self, seed: Seed) -> Result<()> {
+        enemies::shuffle(self, seed)
+    }
+
+    /// Records that the texts offering a check's vanilla item should name the item placed there instead.
+    pub fn rename(&mut self, patch: &Patch, vanilla: Item, item: Item) {
+        let (course, file) = match *patch {
+            Patch::Event { course, name, .. } => (course, name),
+            Patch::Shop(Shop::Ravio(_)) => (Some(course::Id::IndoorLight), "FieldLight_2C_Rental"),
+            Patch::Shop(Shop::Merchant(_)) => (Some(course::Id::FieldLight), "FieldLight_18_StandItem"),
+            _ => return,
+        };
+        self.texts.push(Text { course, file: file.to_string(), vanilla, item });
+    }
+
+    /// Gets every loaded language of a course, or the boot archive if there's no course.
+    fn languages<C>(&mut self, course: C) -> Result<&mut [Language]>
         where
             C: Into<Option<course::Id>>,
     {
         Ok(if let Some(course) = course.into() {
-            &mut self.course(course)?.language
+            &mut self.course(course)?.languages
         } else {
-            &mut self.boot
+            slice::from_mut(&mut self.boot)
         })
     }
 
-    fn flow<C>(&mut self, course: C) -> Result<albw::language::LoadedMut<FlowMut>>
-        where
-            C: Into<Option<course::Id>>,
-    {
-        Ok(self.language(course)?.flow_mut())
-    }
-
     fn parse_args(&mut self, course: course::Id, stage: u16, unq: u16) -> &mut Arg {
         self.scene(course, stage).unwrap()
             .stage_mut()
@@ -112,10 +130,12 @@ impl Patcher {
         match patch {
             Patch::Chest { course, stage, unq } => {
                 self.parse_args(course, stage, unq).0 = item as i32;
+                self.placed.insert((course, stage, unq));
             }
             Patch::Heart { course, scene, unq } |
             Patch::Key { course, scene, unq } => {
                 self.parse_args(course, scene, unq).1 = item as i32;
+                self.placed.insert((course, scene, unq));
             }
             // Patch::Maiamai { course, scene, unq } => {
             //     self.parse_args(course, scene, unq).2 = item as i32;
@@ -125,22 +145,39 @@ impl Patcher {
                 name,
                 index,
             } => {
-                self.flow(course)?
-                    .get_mut(name)
+                let step = self.languages(course)?[0]
+                    .flow()
+                    .get(name)
                     .ok_or_else(|| Error::game("File not found."))??
-                    .get_mut()
-                    .get_mut(index)
-                    .ok_or_else(|| {
-                        Error::game(format!(
-                            "{}/{} [{}] not found",
-                            course.as_ref().map(course::Id::as_str).unwrap_or("Boot"),
-                            name,
-                            index
-                        ))
-                    })?
-                    .into_action()
-                    .ok_or_else(|| Error::game("Not an action."))?
-                    .set_value(item as u32);
+                    .get()
+                    .steps()
+                    .get(index)
+                    .transpose()?;
+                if let Some(Step::Action { command: Action::Other(command, _), .. }) = step {
+                    *self.give_items.entry(command).or_default() += 1;
+                }
+                for language in self.languages(course)? {
+                    language
+                        .flow_mut()
+                        .get_mut(name)
+                        .ok_or_else(|| Error::game("File not found."))??
+                        .get_mut()
+                        .get_mut(index)
+                        .ok_or_else(|| {
+                            Error::game(format!(
+                                "{}/{} [{}] not found",
+                                course.as_ref().map(course::Id::as_str).unwrap_or("Boot"),
+                                name,
+                                index
+                            ))
+                        })?
+                        .into_action()
+                        .ok_or_else(|| Error::game("Not an action."))?
+                        .set_value(item as u32);
+                }
+            }
+            Patch::Upgrade(vanilla) => {
+                self.upgrades.push((vanilla, item));
             }
             Patch::Shop(Shop::Ravio(index)) => {
                 self.rentals[index as usize] = item;
@@ -158,6 +195,58 @@ impl Patcher {
         Ok(())
     }
 
+    /// Makes Mother Maiamai give `item` in place of the Nice item `vanilla`.
+    ///
+    /// Nothing else in the game gives out Nice items, so every action that gives `vanilla` is one of her offers. Actions
+    /// are told apart from the rest by their command, which is taken to be the one most shuffled events give their
+    /// items with. Her cave is in Hyrule, so only the flows of its overworld, caves and houses are searched.
+    fn upgrade(&mut self, vanilla: Item, item: Item) -> Result<()> {
+        let give_item = self
+            .give_items
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .map(|(command, _)| *command)
+            .ok_or_else(|| Error::game("Could not tell which command gives items, no events were patched."))?;
+        let mut found = vec![];
+        for course in [course::Id::FieldLight, course::Id::CaveLight, course::Id::IndoorLight] {
+            for language in self.languages(course)? {
+                let mut offers = vec![];
+                let flows = language.flow();
+                for name in flows.names() {
+                    let flow = flows.get(name).ok_or_else(|| Error::game("File not found."))??;
+                    for (index, step) in flow.get().steps().iter().enumerate() {
+                        if let Step::Action { command: Action::Other(command, value), .. } = step? {
+                            if command == give_item && value == vanilla as u32 {
+                                offers.push((name.to_string(), index as u16));
+                            }
+                        }
+                    }
+                }
+                for (name, index) in offers {
+                    language
+                        .flow_mut()
+                        .get_mut(&name)
+                        .ok_or_else(|| Error::game("File not found."))??
+                        .get_mut()
+                        .get_mut(index)
+                        .and_then(|step| step.into_action())
+                        .ok_or_else(|| Error::game("Not an action."))?
+                        .set_value(item as u32);
+                    found.push((course, name));
+                }
+            }
+        }
+        if found.is_empty() {
+            return Err(Error::game(format!("Could not find where Mother Maiamai gives {}.", vanilla.as_str())));
+        }
+        found.sort_unstable();
+        found.dedup();
+        for (course, file) in found {
+            self.texts.push(Text { course: Some(course), file, vanilla, item });
+        }
+        Ok(())
+    }
+
     pub fn prepare(mut self, settings: &Settings) -> Result<Patches> {
         let mut item_actors = HashMap::new();
         for (item, get_item) in self.game.get_item() {
@@ -177,6 +266,10 @@ impl Patcher {
         scenes::apply(&mut self, settings)?;
         let free = self.rentals[8];
         flow::apply(&mut self, free)?;
+        for (vanilla, item) in mem::take(&mut self.upgrades) {
+            self.upgrade(vanilla, item)?;
+        }
+        messages::apply(&mut self)?;
         {
             let Self {
                 ref rentals,
@@ -204,7 +297,7 @@ impl Patcher {
             kakariko_actors.add(item_actors.get(&merchant[0]).unwrap().clone())?;
             kakariko_actors.add(item_actors.get(&merchant[2]).unwrap().clone())?;
         }
-        let code = code::create(&self);
+        let code = code::create(&self, settings)?;
         let Self {
             game,
             boot,
@@ -213,8 +306,10 @@ impl Patcher {
         } = self;
         let mut romfs = Files(vec![]);
         romfs.add(boot.into_archive());
-        for (_, Course { language, scenes }) in courses {
-            romfs.add(language.into_archive());
+        for (_, Course { languages, scenes }) in courses {
+            for language in languages {
+                romfs.add(language.into_archive());
+            }
             for (_, scene) in scenes {
                 let (actors, stage) = scene.into_files();
                 if let Some(archive) = actors {
@@ -232,10 +327,44 @@ impl Patcher {
 
 #[derive(Debug)]
 pub struct Course {
-    language: Language,
+    languages: Vec<Language>,
     scenes: HashMap<u16, Scene>,
 }
 
+impl Course {
+    /// Loads a course's archive in each of the given languages that the ROM has.
+    fn load(game: &Game, languages: &[&'static str], course: course::Id) -> Result<Self> {
+        let mut loaded = vec![];
+        for language in languages {
+            match game.course(course).language(language) {
+                Ok(language) => loaded.push(language),
+                Err(err) if matches!(err.kind(), albw::ErrorKind::NotFound) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if loaded.is_empty() {
+            return Err(Error::game(format!(
+                "None of the languages {} were found for {}.",
+                languages.join(", "),
+                course.as_str()
+            )));
+        }
+        Ok(Self {
+            languages: loaded,
+            scenes: Default::default(),
+        })
+    }
+}
+
+/// A message file whose mention of a check's vanilla item should be rewritten.
+#[derive(Clone, Debug)]
+struct Text {
+    course: Option<course::Id>,
+    file: String,
+    vanilla: Item,
+    item: Item,
+}
+
 #[derive(Clone, Debug)]
 pub enum Patch {
     Chest {
@@ -263,6 +392,8 @@ pub enum Patch {
     //     scene: u16,
     //     unq: u16,
     // },
+    /// One of Mother Maiamai's offers, found by the Nice item it gives in the vanilla game.
+    Upgrade(Item),
     Shop(Shop),
     // Multi(Vec<Patch>),
     None, // Workaround until everything is shufflable
@@ -280,6 +411,58 @@ pub enum Shop {
     Merchant(u8),
 }
 
+/// How the patched game is written to the output directory.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// A bare `<title ID>` mod folder, to be copied to wherever the loader expects it.
+    LayeredFs(CodeFormat),
+    /// A mod folder at `luma/titles/<title ID>`, ready to copy to the root of a Luma3DS SD card.
+    Luma(CodeFormat),
+    /// A mod folder at `load/mods/<title ID>`, ready to copy into the Citra user folder.
+    Citra(CodeFormat),
+    /// Only the replaced RomFS files under `<title ID>/romfs`, for other tools.
+    RomFs,
+    /// A standalone game image, for setups that can't load LayeredFS mods.
+    Image(ImageFormat),
+}
+
+impl OutputFormat {
+    /// Uses the given code format if writing a mod folder.
+    pub fn with_code(self, code: CodeFormat) -> Self {
+        match self {
+            Self::LayeredFs(_) => Self::LayeredFs(code),
+            Self::Luma(_) => Self::Luma(code),
+            Self::Citra(_) => Self::Citra(code),
+            format => format,
+        }
+    }
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self::LayeredFs(CodeFormat::default())
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "layeredfs" => Ok(Self::default()),
+            "luma" => Ok(Self::Luma(CodeFormat::default())),
+            "citra" => Ok(Self::Citra(CodeFormat::default())),
+            "romfs" => Ok(Self::RomFs),
+            "3ds" | "cci" => Ok(Self::Image(ImageFormat::Cci)),
+            "cxi" => Ok(Self::Image(ImageFormat::Cxi)),
+            _ => Err(format!(
+                "Unknown output format '{}', expected one of: layeredfs, luma, citra, romfs, 3ds, cxi",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Patches {
     game: Game,
@@ -287,47 +470,106 @@ pub struct Patches {
     romfs: Files,
 }
 
-// FIXME unnecssary duplicate
-fn pause() {
-    let mut stdout = stdout();
-    stdout.write(b"\nPress Enter to continue...").unwrap();
-    stdout.flush().unwrap();
-    stdin().read(&mut [0]).unwrap();
-}
-
 impl Patches {
-    pub fn dump<P>(self, path: P) -> Result<()>
+    /// Writes the patched game to the output directory in the given format.
+    pub fn write<P>(self, path: P, format: OutputFormat) -> Result<()>
+        where
+            P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let id = format!("{:016X}", self.game.id());
+        let result = match format {
+            OutputFormat::LayeredFs(code) => self.dump(path.join(id), Some(("", code))),
+            OutputFormat::Luma(code) => self.dump(path.join("luma").join("titles").join(id), Some(("", code))),
+            OutputFormat::Citra(code) => self.dump(path.join("load").join("mods").join(id), Some(("exefs", code))),
+            OutputFormat::RomFs => self.dump(path.join(id), None),
+            OutputFormat::Image(format) => return self.write_image(path, format),
+        };
+        result.map_err(|err| match err.kind() {
+            ErrorKind::Io => Error::with_kind(
+                ErrorKind::OutputNotWritable,
+                format!("Couldn't write to \"{}\": {}", path.display(), err),
+            ),
+            _ => err,
+        })
+    }
+
+    fn write_image<P>(self, path: P, format: ImageFormat) -> Result<()>
         where
             P: AsRef<Path>,
     {
-        let temp = tempdir()?;
-        let moddir = temp.path().join(&format!("{:016X}", self.game.id()));
+        let extension = match format {
+            ImageFormat::Cci => "3ds",
+            ImageFormat::Cxi => "cxi",
+        };
+        let path = path.as_ref().join(format!("{:016X}.{}", self.game.id(), extension));
+        info!("Writing game image to:          {}", path.display());
+        let Self { game, code, romfs } = self;
+        code.validate()?;
+        let exheader = code.exheader(game.exheader());
+        game.write_image(&path, format, &exheader, |data| code.apply(data), romfs.0)?;
+        Ok(())
+    }
+
+    /// Writes a mod folder, with the code patch in the given subfolder and format, if any.
+    fn dump(self, moddir: PathBuf, code: Option<(&str, CodeFormat)>) -> Result<()> {
+        info!("Writing patch to:               {}", moddir.display());
+        clean(&moddir)?;
         let romfs = moddir.join("romfs");
         fs::create_dir_all(&romfs)?;
-        self.code.dump(&moddir, self.game.exheader())?;
+        if let Some((dir, format)) = code {
+            let dir = moddir.join(dir);
+            fs::create_dir_all(&dir)?;
+            let exheader = self.code.dump(&dir, &self.game, format)?;
+            fs::write(moddir.join("exheader.bin"), exheader.as_ref())?;
+        } else {
+            warn!("Only writing RomFS files, the game will not play correctly without the code patch.");
+        }
         for file in self.romfs.0 {
             file.dump(&romfs)?;
         }
-        let path = path.as_ref();
-        info!("Writing patch to:               {}\\{:016X}", path.display(), self.game.id());
-
-        match fs_extra::copy_items(
-            &[moddir],
-            path,
-            &CopyOptions {
-                overwrite: true,
-                ..Default::default()
-            },
-        ).map_err(Error::io) {
-            Ok(_) => Ok(()),
-            Err(_) => {
-                error!("Couldn't write to:              {}", path.display());
-                error!("Please check that config.toml points to a valid output destination.");
-                pause();
-                std::process::exit(1);
-            }
+        Ok(())
+    }
+}
+
+/// Picks the requested languages out of those available, or all of them if none were requested.
+fn select_languages(available: &[&'static str], requested: &[String]) -> Result<Vec<&'static str>> {
+    if requested.is_empty() {
+        return Ok(available.to_vec());
+    }
+    requested
+        .iter()
+        .map(|language| {
+            available
+                .iter()
+                .find(|available| available.eq_ignore_ascii_case(language))
+                .copied()
+                .ok_or_else(|| {
+                    Error::game(format!(
+                        "Unknown language '{}', expected one of: {}",
+                        language,
+                        available.join(", ")
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// Removes everything a previous seed may have left in a mod folder.
+fn clean(moddir: &Path) -> Result<()> {
+    for dir in ["romfs", "exefs"] {
+        let path = moddir.join(dir);
+        if path.is_dir() {
+            fs::remove_dir_all(path)?;
         }
     }
+    for file in ["code.ips", "code.bps", "code.bin", "exheader.bin"] {
+        let path = moddir.join(file);
+        if path.is_file() {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -450,3 +692,35 @@ fn truncate_cutscene(mut demo: Demo) -> Demo {
     demo.finish_mut().set_timestamp(0);
     demo
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_selects_languages() -> Result<()> {
+        let available = &["English", "French", "Spanish"];
+        assert_eq!(select_languages(available, &[])?, available);
+        assert_eq!(select_languages(available, &["french".to_string()])?, ["French"]);
+        assert!(select_languages(available, &["German".to_string()]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn it_cleans_stale_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let moddir = dir.path();
+        fs::create_dir_all(moddir.join("romfs/World"))?;
+        fs::create_dir_all(moddir.join("exefs"))?;
+        fs::write(moddir.join("romfs/World/stale.byaml"), [])?;
+        fs::write(moddir.join("exefs/code.ips"), [])?;
+        fs::write(moddir.join("code.bin"), [])?;
+        fs::write(moddir.join("notes.txt"), [])?;
+        clean(moddir)?;
+        assert!(!moddir.join("romfs").exists());
+        assert!(!moddir.join("exefs").exists());
+        assert!(!moddir.join("code.bin").exists());
+        assert!(moddir.join("notes.txt").exists());
+        Ok(())
+    }
+}
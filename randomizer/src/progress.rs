@@ -117,12 +117,29 @@ impl Progress {
     }
 
     pub fn has_nice_bombs(&self) -> bool {
-        self.has_either(Bombs01, Bombs02) && self.can_get_10_maiamai()
+        if self.settings.logic.nice_items {
+            self.has_both(Bombs01, Bombs02)
+        } else {
+            // Only Mother Maiamai's first upgrade is logical, there's no telling what a player spends their first 10 on
+            self.has_bombs() && self.can_get_10_maiamai()
+        }
+    }
+
+    pub fn has_nice_bow(&self) -> bool {
+        self.has_both(Bow01, Bow02)
+    }
+
+    pub fn has_nice_ice_rod(&self) -> bool {
+        self.has_both(IceRod01, IceRod02)
+    }
+
+    pub fn has_nice_tornado_rod(&self) -> bool {
+        self.has_both(TornadoRod01, TornadoRod02)
     }
 
     // Dirty function to determine if 10 maiamai are available
     // TODO put Maiamai into the world graph and calculate this dynamically
-    fn can_get_10_maiamai(&self) -> bool {
+    pub fn can_get_10_maiamai(&self) -> bool {
 
         // Merge or Boots enable more than 10, easy logic if we have them
         if self.can_merge() || self.has_boots() {
@@ -11,4 +11,17 @@ crate::region! {
             lorule::skull::woods :- can_merge,
         ],
     },
+    maiamai {
+        locations: [
+            "Mother Maiamai (Bow)": ItemBowLv2 @Upgrade(ItemBowLv2) where settings: settings.logic.nice_items,
+            "Mother Maiamai (Boomerang)": ItemBoomerangLv2 @Upgrade(ItemBoomerangLv2) where settings: settings.logic.nice_items,
+            "Mother Maiamai (Hookshot)": ItemHookShotLv2 @Upgrade(ItemHookShotLv2) where settings: settings.logic.nice_items,
+            "Mother Maiamai (Bombs)": ItemBombLv2 @Upgrade(ItemBombLv2) where settings: settings.logic.nice_items,
+            "Mother Maiamai (Fire Rod)": ItemFireRodLv2 @Upgrade(ItemFireRodLv2) where settings: settings.logic.nice_items,
+            "Mother Maiamai (Ice Rod)": ItemIceRodLv2 @Upgrade(ItemIceRodLv2) where settings: settings.logic.nice_items,
+            "Mother Maiamai (Hammer)": ItemHammerLv2 @Upgrade(ItemHammerLv2) where settings: settings.logic.nice_items,
+            "Mother Maiamai (Sand Rod)": ItemSandRodLv2 @Upgrade(ItemSandRodLv2) where settings: settings.logic.nice_items,
+            "Mother Maiamai (Tornado Rod)": ItemTornadeRodLv2 @Upgrade(ItemTornadeRodLv2) where settings: settings.logic.nice_items,
+        ],
+    },
 }
@@ -188,12 +188,12 @@ macro_rules! subregion {
             pub fn patch(patcher: &mut Patcher, layout: &crate::Layout, settings: &$crate::Settings) -> crate::Result<()> {
                 $(use crate::patch::Patch;
                 $(if $crate::settings_check!($($settings $where)?)(settings) {
-                    crate::patch!($variant $props).apply(
-                        patcher,
-                        layout
-                            .get(&crate::LocationInfo::new(SUBREGION, $key))
-                            .unwrap_or_else(|| unreachable!(stringify!($key))),
-                    )?;
+                    let patch = crate::patch!($variant $props);
+                    let item = layout
+                        .get(&crate::LocationInfo::new(SUBREGION, $key))
+                        .unwrap_or_else(|| unreachable!(stringify!($key)));
+                    patcher.rename(&patch, albw::Item::$item, item);
+                    patch.apply(patcher, item)?;
                 })*)?
                 Ok(())
             }
@@ -297,6 +297,9 @@ macro_rules! patch {
     (Maiamai($scene:literal[$unq:literal])) => {
         Patch::Maiamai { course: COURSE, scene: $scene - 1, unq: $unq }
     };
+    (Upgrade($item:ident)) => {
+        Patch::Upgrade(albw::Item::$item)
+    };
     (Shop($variant:ident$($args:tt)?)) => {
         Patch::Shop(crate::patch::Shop::$variant $($args)?)
     };
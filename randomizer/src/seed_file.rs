@@ -0,0 +1,222 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use albw::Item;
+use serde::{Deserialize, Serialize};
+
+use crate::{item_name, world::build_world_graph, Error, ItemExt, Layout, Result, Seed, Settings, Spoiler};
+
+/// A shareable description of a generated seed that holds no game data.
+///
+/// Applying it to a player's own ROM rebuilds the same patch: every placement is pinned by check name, and the seed
+/// and settings drive everything else the patcher randomizes.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SeedFile {
+    version: String,
+    hash: String,
+    seed: Seed,
+    settings: Settings,
+    placements: BTreeMap<String, String>,
+}
+
+impl SeedFile {
+    pub(crate) fn new(seed: Seed, settings: &Settings, layout: &Layout) -> Self {
+        let mut placements = BTreeMap::new();
+        for node in build_world_graph().into_values() {
+            for check in node.get_checks() {
+                if let Some(item) = check.get_location_info().and_then(|location| layout.get(&location)) {
+                    placements.insert(check.get_name().to_string(), item.as_str().to_string());
+                }
+            }
+        }
+        let mut file = Self {
+            version: VERSION.to_string(),
+            hash: String::new(),
+            seed,
+            settings: settings.clone(),
+            placements,
+        };
+        file.hash = file.compute_hash();
+        file
+    }
+
+    /// Reads a seed file, rejecting it if it was made by another version or its checksum doesn't match.
+    ///
+    /// The checksum is a CRC32, which catches a file damaged in transit or edited by hand, not one altered on purpose.
+    pub fn read<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file: Self = serde_json::from_slice(&fs::read(path)?)
+            .map_err(|err| Error::game(format!("Invalid seed file: {}", err)))?;
+        if file.version != VERSION {
+            return Err(Error::game(format!(
+                "Seed file was made with version {}, but this is version {}.",
+                file.version, VERSION
+            )));
+        }
+        if file.hash != file.compute_hash() {
+            return Err(Error::game("Seed file checksum did not match, it may be corrupted or edited."));
+        }
+        Ok(file)
+    }
+
+    /// Reads a spoiler log as a seed file, so either can be used to rebuild a seed.
+    ///
+    /// The spoiler log names items the way players know them, which doesn't tell the four Master Ores apart. Items
+    /// sharing a name are handed out in turn, so the rebuilt seed may have its ores swapped around.
+    pub fn read_spoiler<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        type Worlds = BTreeMap<String, BTreeMap<String, BTreeMap<String, String>>>;
+        #[derive(Deserialize)]
+        struct Log {
+            seed: Seed,
+            settings: Settings,
+            layout: Worlds,
+        }
+
+        let log: Log = serde_json::from_slice(&fs::read(path)?)
+            .map_err(|err| Error::game(format!("Invalid spoiler log: {}", err)))?;
+        let mut locations = build_world_graph()
+            .into_values()
+            .flat_map(|node| node.get_checks())
+            .filter_map(|check| check.get_location_info())
+            .collect::<Vec<_>>();
+        locations.sort_by_key(|location| (location.world() as u8, location.region(), location.name()));
+
+        let mut layout = Layout::default();
+        let mut handed_out = BTreeMap::<&str, usize>::new();
+        for location in locations {
+            let item = match log
+                .layout
+                .get(&format!("{:?}", location.world()))
+                .and_then(|world| world.get(location.region()))
+                .and_then(|region| region.get(location.name()))
+            {
+                Some(item) => item.as_str(),
+                None => continue,
+            };
+            let candidates = Item::iter()
+                .filter(|candidate| candidate.normalize() == *candidate && item_name(candidate) == Some(item))
+                .collect::<Vec<_>>();
+            if candidates.is_empty() {
+                return Err(Error::game(format!("Unknown item in spoiler log: {}", item)));
+            }
+            let count = handed_out.entry(item).or_default();
+            layout.set(location, candidates[*count % candidates.len()]);
+            *count += 1;
+        }
+
+        let placed = handed_out.values().sum::<usize>();
+        let listed = log.layout.values().flat_map(|world| world.values()).map(|region| region.len()).sum::<usize>();
+        if placed != listed {
+            return Err(Error::game(format!(
+                "Spoiler log has {} checks this version doesn't know.",
+                listed - placed
+            )));
+        }
+        Ok(Self::new(log.seed, &log.settings, &layout))
+    }
+
+    pub fn write<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let json = serde_json::to_vec_pretty(self).expect("Could not serialize the seed file.");
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    pub fn seed(&self) -> Seed {
+        self.seed
+    }
+
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    /// Rebuilds the spoiler this file was made from, ready to patch a ROM.
+    pub fn spoiler(&self) -> Result<Spoiler<'_>> {
+        let locations = build_world_graph()
+            .into_values()
+            .flat_map(|node| node.get_checks())
+            .filter_map(|check| Some((check.get_name(), check.get_location_info()?)))
+            .collect::<BTreeMap<_, _>>();
+        let mut layout = Layout::default();
+        for (check, item) in &self.placements {
+            let location = locations
+                .get(check.as_str())
+                .ok_or_else(|| Error::game(format!("Unknown check in seed file: {}", check)))?;
+            let item = Item::iter()
+                .find(|candidate| candidate.as_str() == item)
+                .ok_or_else(|| Error::game(format!("Unknown item in seed file: {}", item)))?;
+            layout.set(*location, item);
+        }
+        Ok(Spoiler {
+            seed: self.seed,
+            settings: &self.settings,
+            layout,
+        })
+    }
+
+    /// A CRC32 checksum of everything else in the file.
+    fn compute_hash(&self) -> String {
+        let contents = serde_json::to_vec(&(&self.version, self.seed, &self.settings, &self.placements))
+            .expect("Could not serialize the seed file.");
+        format!("{:08X}", crc::crc32::checksum_ieee(&contents))
+    }
+}
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filler_new;
+
+    #[test]
+    fn it_round_trips() -> Result<()> {
+        let settings = Settings::default();
+        let spoiler = (0..20)
+            .find_map(|seed| std::panic::catch_unwind(|| filler_new(&settings, seed)).ok())
+            .expect("no seed could be generated");
+        let file = spoiler.seed_file();
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("seed.z17r");
+        file.write(&path)?;
+
+        let read = SeedFile::read(&path)?;
+        assert_eq!(read.hash(), file.hash());
+        assert_eq!(read.spoiler()?.seed_file().placements, file.placements);
+
+        let tampered = fs::read_to_string(&path)?.replacen("\"seed\": ", "\"seed\": 1", 1);
+        fs::write(&path, tampered)?;
+        assert!(SeedFile::read(&path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn it_reads_spoiler_logs() -> Result<()> {
+        let settings = Settings::default();
+        let spoiler = (0..20)
+            .find_map(|seed| std::panic::catch_unwind(|| filler_new(&settings, seed)).ok())
+            .expect("no seed could be generated");
+        let file = spoiler.seed_file();
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("spoiler.json");
+        serde_json::to_writer(fs::File::create(&path)?, &spoiler).unwrap();
+
+        let read = SeedFile::read_spoiler(&path)?;
+        assert_eq!(read.seed(), file.seed());
+        // Master Ores can't be told apart in the log, so only where an ore is matters
+        let ores = |placements: &BTreeMap<String, String>| {
+            placements
+                .iter()
+                .map(|(check, item)| (check.clone(), if item.starts_with("Ore") { "Ore" } else { item }.to_string()))
+                .collect::<BTreeMap<_, _>>()
+        };
+        assert_eq!(ores(&read.placements), ores(&file.placements));
+        Ok(())
+    }
+}
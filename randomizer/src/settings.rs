@@ -1,41 +1,203 @@
-use std::{
-    collections::{HashMap, HashSet},
-    hash::{Hash, Hasher},
-};
+use std::collections::BTreeSet;
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
 
+use schemars::{schema::RootSchema, schema_for, JsonSchema};
 use serde::{Deserialize, Serialize};
+use sys::{Preset, Table};
+use toml::Value;
 
-use crate::{LocationInfo, regions};
+use crate::filler_item::FillerItem::{self, *};
 use crate::logic_mode::LogicMode;
 
+/// The version of the preset format this version of the randomizer reads and writes.
+pub const VERSION: u32 = 2;
+
 /// Logic and behavior settings.
-#[derive(Clone, Debug, Default, Deserialize, Hash, Serialize)]
+#[derive(Clone, Debug, Deserialize, Hash, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct Settings {
+    /// The version of the preset format. Presets from older versions are upgraded when loaded.
+    pub version: u32,
     pub logic: Logic,
     pub options: Options,
     pub exclusions: Exclusion,
-    #[serde(skip_serializing_if = "Exclude::is_empty")]
-    pub exclude: Exclude,
+    pub rules: Vec<PlacementRule>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            logic: Default::default(),
+            options: Default::default(),
+            exclusions: Default::default(),
+            rules: Default::default(),
+        }
+    }
 }
 
 impl Settings {
-    pub fn is_excluded(&self, location: &LocationInfo) -> bool {
-        let world = match location.world() {
-            regions::World::Hyrule => &self.exclude.hyrule,
-            regions::World::Lorule => &self.exclude.lorule,
-            regions::World::Dungeons => &self.exclude.dungeons,
+    /// Generates a JSON Schema of the current preset format, for frontends to validate settings against.
+    pub fn schema() -> RootSchema {
+        schema_for!(Settings)
+    }
+
+    /// Checks for settings that can't be filled, such as unknown check names, more exclusions than there is junk
+    /// to fill them with, or placement rules that can't all be satisfied.
+    ///
+    /// Every problem found is returned, rather than only the first.
+    pub fn validate(&self) -> Result<(), InvalidSettings> {
+        if self.version != VERSION {
+            return Err(InvalidSettings(vec![format!(
+                "These settings are version {}, but this version of the randomizer uses version {}. Load them as a preset to upgrade them.",
+                self.version, VERSION
+            )]));
+        }
+        let problems = crate::filler::validate(self);
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidSettings(problems))
+        }
+    }
+
+    /// Every placement rule in effect, including those implied by the `logic` toggles.
+    pub fn placement_rules(&self) -> Vec<PlacementRule> {
+        // Ravio (6) is left out as it only opens up after meeting Sahasrahla
+        let ravio = |item, one_of| PlacementRule {
+            item,
+            one_of,
+            within: vec!["ravio".to_string()],
+            not_in: vec!["Ravio (6)".to_string()],
         };
-        world
-            .0
-            .get(location.region())
-            .map(|region| region.contains(location.name()))
-            .unwrap_or(false)
+
+        let mut rules = Vec::new();
+        if self.logic.bow_of_light_in_castle {
+            rules.push(PlacementRule {
+                item: Some(BowOfLight),
+                within: vec!["[LC]".to_string()],
+                ..Default::default()
+            });
+        }
+        if self.logic.assured_weapon {
+            let mut weapons = vec![Bow01, Bombs01, FireRod01, IceRod01, Hammer01];
+            if !self.logic.swordless_mode {
+                weapons.extend([Sword01, Sword02, Sword03, Sword04]);
+            }
+            if self.logic.mode != LogicMode::Normal {
+                weapons.extend([Lamp01, Net01]);
+            }
+            rules.push(ravio(None, weapons));
+        }
+        if self.logic.bell_in_shop {
+            rules.push(ravio(Some(Bell), Vec::new()));
+        }
+        if self.logic.pouch_in_shop {
+            rules.push(ravio(Some(Pouch), Vec::new()));
+        }
+        if self.logic.boots_in_shop {
+            rules.push(ravio(Some(PegasusBoots), Vec::new()));
+        }
+        rules.extend(self.rules.iter().cloned());
+        rules
+    }
+}
+
+/// The problems that stop a set of settings from being used, each explained on its own line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidSettings(Vec<String>);
+
+impl InvalidSettings {
+    pub fn problems(&self) -> &[String] {
+        &self.0
+    }
+}
+
+impl Display for InvalidSettings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.join("\n"))
+    }
+}
+
+impl StdError for InvalidSettings {}
+
+impl Preset for Settings {
+    const JOINED: &'static [&'static str] = &["exclusions.exclusions", "exclusions.priority", "rules"];
+    const KEPT: &'static [&'static str] = &["version"];
+
+    fn migrate(table: &mut Table) -> sys::Result<Vec<String>> {
+        let version = match table.get("version") {
+            None => 1,
+            Some(Value::Integer(version)) if (1..=VERSION as i64).contains(version) => *version as u32,
+            Some(Value::Integer(version)) if *version > VERSION as i64 => {
+                return Err(sys::Error::new(format!(
+                    "Preset is version {}, but this version of the randomizer only reads up to version {}.",
+                    version, VERSION
+                )));
+            }
+            Some(version) => return Err(sys::Error::new(format!("Invalid preset version: {}", version))),
+        };
+        let mut warnings = Vec::new();
+        for migrate in &MIGRATIONS[version as usize - 1..] {
+            migrate(table, &mut warnings);
+        }
+        table.insert("version".to_string(), Value::Integer(VERSION.into()));
+        Ok(warnings)
+    }
+}
+
+/// Upgrades a preset to the next version, the first one upgrading from version 1.
+const MIGRATIONS: [fn(&mut Table, &mut Vec<String>); VERSION as usize - 1] = [from_v1];
+
+/// Version 1 had no `version` key. Besides `[exclusions]`, it named excluded checks by world and region under
+/// `[exclude.<World>]`, and `[exclusions]` took lists under any name but only read two of them.
+fn from_v1(table: &mut Table, warnings: &mut Vec<String>) {
+    if let Some(Value::Table(exclusions)) = table.get_mut("exclusions") {
+        let unused = exclusions
+            .keys()
+            .filter(|key| !matches!(key.as_str(), "exclusions" | "priority"))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in unused {
+            exclusions.remove(&key);
+            warnings.push(format!(
+                "\"{}\" under [exclusions] was never used and is now ignored. Only \"exclusions\" and \"priority\" are read.",
+                key
+            ));
+        }
+    }
+
+    if let Some(exclude) = table.remove("exclude") {
+        let checks = exclude
+            .as_table()
+            .into_iter()
+            .flat_map(|worlds| worlds.values())
+            .filter_map(Value::as_table)
+            .flat_map(|regions| regions.values())
+            .filter_map(Value::as_array)
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>();
+        let exclusions = table
+            .entry("exclusions")
+            .or_insert_with(|| Value::Table(Table::new()));
+        if let Some(exclusions) = exclusions.as_table_mut() {
+            let excluded = exclusions
+                .entry("exclusions")
+                .or_insert_with(|| Value::Array(Vec::new()));
+            if let Some(excluded) = excluded.as_array_mut() {
+                excluded.extend(checks);
+            }
+        }
+        warnings.push(
+            "[exclude] is deprecated, its checks were moved to the \"exclusions\" list under [exclusions].".to_string(),
+        );
     }
 }
 
 /// Settings to change the randomizer's logic checks.
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct Logic {
     /// Logic to use for item placement (Normal, Hard, Glitched (Basic, Advanced, Hell), No Logic)
@@ -54,6 +216,8 @@ pub struct Logic {
     pub swordless_mode: bool,
     /// Shuffle Super Lamp and Super Net
     pub super_items: bool,
+    /// Shuffle a second copy of each of Ravio's items, which upgrades it to its Nice version
+    pub nice_items: bool,
     /// Skip Trials Door in Lorule Castle
     pub skip_trials: bool,
     /// Guarantees Bow of Light will be placed in Lorule Castle
@@ -63,137 +227,114 @@ pub struct Logic {
 }
 
 /// Settings to change the randomizer's logic checks.
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Deserialize, JsonSchema, Serialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct Options {
     /// Experimental: Change Hyrule to the nighttime color scheme (until visiting Lorule)
     pub night_mode: bool,
+    /// Shuffle enemies across every scene with others of the same kind
+    pub enemy_shuffle: bool,
+    /// Only write these languages (e.g. "English"), or every language the ROM has if empty. Languages left out aren't
+    /// patched at all, so their events keep their vanilla items and the game shouldn't be played in them.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub languages: Vec<String>,
 }
 
-/// A setting for useless items.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
-pub enum Skippable {
-    Unchanged,
-    Shuffled,
-    Skip,
-}
-
-impl Skippable {
-    pub fn is_shuffled(&self) -> bool {
-        *self == Self::Shuffled
-    }
-
-    pub fn is_skipped(&self) -> bool {
-        *self == Self::Skip
-    }
-}
-
-impl Default for Skippable {
-    fn default() -> Self {
-        Self::Unchanged
-    }
-}
-
-/// A setting for progression items.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
-pub enum Progression {
-    Unchanged,
-    Shuffled,
+/// Constrains where the filler may place an item.
+///
+/// Either `item` or `one_of` names the item. With `one_of`, a single randomly chosen item from the list is
+/// constrained. `in` and `not_in` take the same names and groups as the exclusion list.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlacementRule {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item: Option<FillerItem>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub one_of: Vec<FillerItem>,
+    #[serde(rename = "in", skip_serializing_if = "Vec::is_empty")]
+    pub within: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub not_in: Vec<String>,
 }
 
-impl Progression {
-    pub fn is_shuffled(&self) -> bool {
-        *self == Self::Shuffled
-    }
+/// Checks named under `exclusions` never hold progression, checks named under `priority` always do.
+///
+/// Entries may be check names, regions, worlds, dungeon abbreviations (`[EP]`) or tags (`minigame`, `boss`, `npc`,
+/// `shop`, `treasure-dungeon`).
+#[derive(Clone, Debug, Default, Deserialize, Hash, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Exclusion {
+    pub exclusions: BTreeSet<String>,
+    pub priority: BTreeSet<String>,
 }
 
-impl Default for Progression {
-    fn default() -> Self {
-        Self::Unchanged
+impl Exclusion {
+    pub fn excluded(&self) -> impl Iterator<Item=&String> {
+        self.exclusions.iter()
     }
-}
-
-/// A setting for the castle barrier.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
-pub enum Barrier {
-    Unchanged,
-    Start,
-}
 
-impl Barrier {
-    pub fn is_start(&self) -> bool {
-        *self == Self::Start
+    pub fn priority(&self) -> impl Iterator<Item=&String> {
+        self.priority.iter()
     }
 }
 
-impl Default for Barrier {
-    fn default() -> Self {
-        Self::Unchanged
+pub fn open_default() -> Settings {
+    Settings {
+        ..Default::default()
     }
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Hash, Serialize)]
-#[serde(default, deny_unknown_fields)]
-pub struct Exclude {
-    #[serde(rename = "Hyrule", skip_serializing_if = "World::is_empty")]
-    hyrule: World,
-    #[serde(rename = "Lorule", skip_serializing_if = "World::is_empty")]
-    lorule: World,
-    #[serde(rename = "Dungeons", skip_serializing_if = "World::is_empty")]
-    dungeons: World,
-}
-
-impl Exclude {
-    fn is_empty(&self) -> bool {
-        self.hyrule.is_empty() && self.lorule.is_empty() && self.dungeons.is_empty()
+pub fn plando_settings() -> Settings {
+    Settings {
+        logic: Logic {
+            ..Default::default()
+        },
+        ..Default::default()
     }
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct Exclusion(pub(crate) HashMap<String, HashSet<String>>);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl Hash for Exclusion {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        for (key, value) in self.0.iter() {
-            key.hash(state);
-            for location in value.iter() {
-                location.hash(state);
-            }
-        }
-    }
-}
+    #[test]
+    fn it_migrates_v1_presets() {
+        let mut table = toml::from_str::<Table>(r#"
+            [logic]
+            bell_in_shop = true
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct World(HashMap<String, HashSet<String>>);
+            [exclusions]
+            exclusions = ["Dampe"]
+            unused = ["Rosso"]
 
-impl World {
-    fn is_empty(&self) -> bool {
-        self.0.is_empty()
-    }
-}
+            [exclude.Hyrule]
+            "Lost Woods" = ["Master Sword Pedestal"]
+        "#).unwrap();
+        let warnings = Settings::migrate(&mut table).unwrap();
+        assert_eq!(warnings.len(), 2);
 
-impl Hash for World {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        for (key, value) in self.0.iter() {
-            key.hash(state);
-            for location in value.iter() {
-                location.hash(state);
-            }
-        }
+        let settings: Settings = Value::Table(table).try_into().unwrap();
+        assert_eq!(settings.version, VERSION);
+        assert!(settings.logic.bell_in_shop);
+        assert_eq!(
+            settings.exclusions.excluded().collect::<Vec<_>>(),
+            ["Dampe", "Master Sword Pedestal"]
+        );
     }
-}
 
-pub fn open_default() -> Settings {
-    Settings {
-        ..Default::default()
+    #[test]
+    fn it_rejects_newer_presets() {
+        let mut table = toml::from_str::<Table>(&format!("version = {}", VERSION + 1)).unwrap();
+        assert!(Settings::migrate(&mut table).is_err());
     }
-}
 
-pub fn plando_settings() -> Settings {
-    Settings {
-        logic: Logic {
-            ..Default::default()
-        },
-        ..Default::default()
+    #[test]
+    fn it_matches_the_committed_schema() {
+        let schema = serde_json::to_value(Settings::schema()).unwrap();
+        let committed: serde_json::Value = serde_json::from_str(include_str!("../../docs/settings.schema.json")).unwrap();
+        assert_eq!(
+            schema, committed,
+            "docs/settings.schema.json is out of date, regenerate it with `z17-randomizer schema`"
+        );
     }
 }
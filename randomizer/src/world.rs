@@ -602,6 +602,7 @@ fn hyrule() -> HashMap<Location, LocationNode> {
             ),
         ], vec![
             path_free(HyruleField),
+            path_free(MaiamaiCave),
             path(MasterSwordArea,
                  Some(|p| p.has_all_pendants()),
                  None,
@@ -610,6 +611,74 @@ fn hyrule() -> HashMap<Location, LocationNode> {
                  None,
             ),
         ])),
+        (MaiamaiCave, location("Maiamai Cave", vec![
+            // Her offers are only checks when Nice items are shuffled, the filler drops them otherwise
+            check(LocationInfo::new(regions::hyrule::lost::maiamai::SUBREGION, "Mother Maiamai (Bow)"),
+                  Some(|p| p.has_bow() && p.can_get_10_maiamai()),
+                  None,
+                  None,
+                  None,
+                  None,
+            ),
+            check(LocationInfo::new(regions::hyrule::lost::maiamai::SUBREGION, "Mother Maiamai (Boomerang)"),
+                  Some(|p| p.has_boomerang() && p.can_get_10_maiamai()),
+                  None,
+                  None,
+                  None,
+                  None,
+            ),
+            check(LocationInfo::new(regions::hyrule::lost::maiamai::SUBREGION, "Mother Maiamai (Hookshot)"),
+                  Some(|p| p.has_hookshot() && p.can_get_10_maiamai()),
+                  None,
+                  None,
+                  None,
+                  None,
+            ),
+            check(LocationInfo::new(regions::hyrule::lost::maiamai::SUBREGION, "Mother Maiamai (Bombs)"),
+                  Some(|p| p.has_bombs() && p.can_get_10_maiamai()),
+                  None,
+                  None,
+                  None,
+                  None,
+            ),
+            check(LocationInfo::new(regions::hyrule::lost::maiamai::SUBREGION, "Mother Maiamai (Fire Rod)"),
+                  Some(|p| p.has_fire_rod() && p.can_get_10_maiamai()),
+                  None,
+                  None,
+                  None,
+                  None,
+            ),
+            check(LocationInfo::new(regions::hyrule::lost::maiamai::SUBREGION, "Mother Maiamai (Ice Rod)"),
+                  Some(|p| p.has_ice_rod() && p.can_get_10_maiamai()),
+                  None,
+                  None,
+                  None,
+                  None,
+            ),
+            check(LocationInfo::new(regions::hyrule::lost::maiamai::SUBREGION, "Mother Maiamai (Hammer)"),
+                  Some(|p| p.has_hammer() && p.can_get_10_maiamai()),
+                  None,
+                  None,
+                  None,
+                  None,
+            ),
+            check(LocationInfo::new(regions::hyrule::lost::maiamai::SUBREGION, "Mother Maiamai (Sand Rod)"),
+                  Some(|p| p.has_sand_rod() && p.can_get_10_maiamai()),
+                  None,
+                  None,
+                  None,
+                  None,
+            ),
+            check(LocationInfo::new(regions::hyrule::lost::maiamai::SUBREGION, "Mother Maiamai (Tornado Rod)"),
+                  Some(|p| p.has_tornado_rod() && p.can_get_10_maiamai()),
+                  None,
+                  None,
+                  None,
+                  None,
+            ),
+        ], vec![
+            path_free(LostWoods),
+        ])),
         (MasterSwordArea, location("Master Sword Area", vec![
             check_free(LocationInfo::new(regions::hyrule::lost::woods::SUBREGION, "Master Sword Pedestal")),
         ], vec![
@@ -1315,7 +1384,7 @@ fn lorule() -> HashMap<Location, LocationNode> {
         (BootsDungeon, location("Boots Treasure Dungeon", vec![
             check(LocationInfo::new(regions::lorule::field::main::SUBREGION, "Boots Treasure Dungeon"),
                   Some(|p| p.has_boots()),
-                  Some(|p| p.has_master_sword() || p.has_bombs() || p.has_boomerang()), // we're not set up for Nice Ice Rod or Nice Bow yet...
+                  Some(|p| p.has_master_sword() || p.has_bombs() || p.has_boomerang() || p.has_nice_ice_rod() || p.has_nice_bow()),
                   None,
                   None,
                   None,
@@ -2237,7 +2306,7 @@ fn inside_hyrule_castle() -> HashMap<Location, LocationNode> {
             path_free(HyruleCastleRoof),
             path(LoruleBlacksmith,
                  Some(|p| (p.has_bow() || p.has_ice_rod()) && p.can_defeat_yuga2()),
-                 Some(|p| p.has_nice_bombs()), // not counting Nice TRod yet
+                 Some(|p| p.has_nice_bombs() || p.has_nice_tornado_rod()),
                  None,
                  None,
                  None,
@@ -1,4 +1,5 @@
 use std::{
+    env,
     error::Error as StdError,
     fmt::{self, Display, Formatter},
     fs, io,
@@ -6,8 +7,11 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use log::info;
+use directories::ProjectDirs;
+use log::{info, warn};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+pub use toml::value::Table;
+use toml::Value;
 
 use prelude::*;
 
@@ -49,65 +53,246 @@ impl From<io::Error> for Error {
     }
 }
 
+/// A preset format that can upgrade presets written for older versions of it.
+pub trait Preset: DeserializeOwned {
+    /// Dotted paths of the lists that add up across the presets a preset extends, instead of being replaced.
+    const JOINED: &'static [&'static str] = &[];
+
+    /// Top-level keys that are saved even when the presets a preset extends have the same values.
+    const KEPT: &'static [&'static str] = &[];
+
+    /// Upgrades a preset to the current format in place, returning a warning for each deprecated key it changed.
+    ///
+    /// Each file is upgraded on its own, before the presets it extends are merged in.
+    fn migrate(table: &mut Table) -> Result<Vec<String>>;
+}
+
+/// The environment variable that overrides where the config and presets are kept.
+pub const CONFIG_DIR_VAR: &str = "Z17R_CONFIG_DIR";
+
 /// An abstraction over platform-specific functionality.
 #[derive(Debug)]
 pub struct System<P> {
     config: PathBuf,
+    builtins: &'static [(&'static str, &'static str)],
     presets: PhantomData<P>,
 }
 
 impl<P> System<P> {
-    pub fn new() -> Result<Self>
-        where
-            P: Serialize,
-    {
+    /// Opens the config directory, creating it with the built-in presets on first run.
+    ///
+    /// The directory is the first of:
+    /// - `config`, if given;
+    /// - the directory named by the `Z17R_CONFIG_DIR` environment variable;
+    /// - the working directory, if it already has a `config.toml` or `presets` folder, so portable setups keep
+    ///   working;
+    /// - the platform's config directory, such as `~/.config/z17-randomizer` on Linux.
+    ///
+    /// `builtins` are `(name, contents)` pairs. They can be loaded by name even when they aren't in the presets
+    /// folder, and a preset file with the same name takes precedence over them.
+    pub fn new(config: Option<PathBuf>, builtins: &'static [(&'static str, &'static str)]) -> Result<Self> {
+        let config = match config {
+            Some(config) => config,
+            None => config_dir()?,
+        };
+        let system = Self { config, builtins, presets: PhantomData };
+        system.create()?;
+        Ok(system)
+    }
 
-        // let config = PathBuf::from("");
-        // // let config = config_dir()
-        // //     .ok_or_else(|| Error::new("Could not find suitable configuration directory."))?;
-        // if !config.exists() {
-        //     let presets_dir = config.join("presets");
-        //     fs::create_dir_all(&presets_dir)?;
-        //     for (name, preset) in
-        //     iter::once_with(standard_preset).chain(presets.into_iter().map(|(name, preset)| {
-        //         (
-        //             name,
-        //             toml::to_string_pretty(&preset).expect("Could not create builtin presets."),
-        //         )
-        //     }))
-        //     {
-        //         fs::write(presets_dir.join(format!("{}.toml", name)), preset)?
-        //     }
-        // }
-
-        Ok(Self {
-            config: PathBuf::from(""),
-            presets: PhantomData,
-        })
+    /// Creates the presets folder with the built-in presets, if it doesn't exist yet.
+    ///
+    /// Built-in presets are only written the first time, so deleting or editing them sticks.
+    fn create(&self) -> Result<()> {
+        let presets = self.presets_dir();
+        if presets.exists() {
+            return Ok(());
+        }
+        info!("Creating presets folder at:     {}", presets.display());
+        fs::create_dir_all(&presets)
+            .map_err(|err| Error::new(format!("Couldn't create \"{}\": {}", presets.display(), err)))?;
+        for (name, preset) in self.builtins {
+            fs::write(presets.join(format!("{}.toml", name)), preset)?;
+        }
+        Ok(())
+    }
+
+    /// The directory holding `config.toml` and the presets folder.
+    pub fn config_dir(&self) -> &Path {
+        &self.config
+    }
+
+    /// The folder presets are looked up in by name.
+    pub fn presets_dir(&self) -> PathBuf {
+        self.config.join("presets")
     }
 
+    /// Loads a preset by name from the presets folder, or the built-in preset of that name if there's no such file,
+    /// with any presets it extends merged in.
     pub fn preset(&self, name: &str) -> Result<P>
     where
-        P: DeserializeOwned,
+        P: Preset,
     {
-        let path = self.config.join("presets").join(format!("{}.toml", name));
+        let (source, data) = self.read_preset(name)?;
+        info!("Loading preset from:            {}\n", source);
+        self.parse_preset(&source, data)
+    }
+
+    /// Loads a preset from any file, rather than by name from the presets folder.
+    ///
+    /// Presets it extends are still looked up by name in the presets folder.
+    pub fn preset_file<Q>(&self, path: Q) -> Result<P>
+    where
+        P: Preset,
+        Q: AsRef<Path>,
+    {
+        let path = path.as_ref();
 
         info!("Loading preset from:            {}\n", path.display());
 
-        toml::from_slice(&fs::read(
-            path,
-        )?)
-        .map_err(Error::new)
+        let data = fs::read_to_string(path)
+            .map_err(|err| Error::new(format!("Couldn't read preset \"{}\": {}", path.display(), err)))?;
+        self.parse_preset(&path.display().to_string(), data)
+    }
+
+    fn parse_preset(&self, source: &str, data: String) -> Result<P>
+    where
+        P: Preset,
+    {
+        Value::Table(self.resolve_preset(source, &data, &mut Vec::new())?)
+            .try_into()
+            .map_err(|err| Error::new(format!("Invalid preset \"{}\": {}", source, err)))
+    }
+
+    /// Writes a preset to the presets folder, replacing any preset file of the same name, and returns its path.
+    ///
+    /// A preset that extends others only keeps what differs from them, along with its `extends` key. Comments from
+    /// the file it replaces aren't kept.
+    pub fn save_preset(&self, name: &str, preset: &P, extends: &[String]) -> Result<PathBuf>
+    where
+        P: Preset + Serialize,
+    {
+        if name.is_empty() || name.contains(|c: char| matches!(c, '/' | '\\' | ':') || c.is_control()) {
+            return Err(Error::new(format!("\"{}\" can't be used as a preset name.", name)));
+        }
+        let mut table = to_table(preset)?;
+        if !extends.is_empty() {
+            let mut inherited = Table::new();
+            for parent in extends {
+                let (source, data) = self.read_preset(parent)?;
+                merge(&mut inherited, self.resolve_preset(&source, &data, &mut Vec::new())?, P::JOINED, "");
+            }
+            // Going through the preset fills in the defaults, so that settings left at them aren't written out
+            let inherited = Value::Table(inherited)
+                .try_into::<P>()
+                .map_err(|err| Error::new(format!("Invalid preset \"{}\": {}", extends.join(", "), err)))
+                .and_then(|inherited| to_table(&inherited))?;
+            let kept = P::KEPT
+                .iter()
+                .filter_map(|key| table.get(*key).map(|value| (key.to_string(), value.clone())))
+                .collect::<Vec<_>>();
+            strip(&mut table, &inherited, P::JOINED, "")?;
+            table.extend(kept);
+            table.insert(
+                "extends".to_string(),
+                Value::Array(extends.iter().cloned().map(Value::String).collect()),
+            );
+        }
+        // Going through a value puts plain values ahead of tables, as TOML requires
+        let preset = toml::to_string_pretty(&Value::Table(table)).map_err(Error::new)?;
+        let path = self.presets_dir().join(format!("{}.toml", name));
+        fs::write(&path, preset)
+            .map_err(|err| Error::new(format!("Couldn't write preset \"{}\": {}", path.display(), err)))?;
+        Ok(path)
+    }
+
+    /// Gets the names of the presets a preset extends, in order.
+    pub fn extends(&self, name: &str) -> Result<Vec<String>> {
+        let (source, data) = self.read_preset(name)?;
+        parents(&source, &mut parse_table(&source, &data)?)
+    }
+
+    /// Reads a preset by name, returning where it was read from along with its contents.
+    fn read_preset(&self, name: &str) -> Result<(String, String)> {
+        let path = self.presets_dir().join(format!("{}.toml", name));
+        if path.exists() {
+            let data = fs::read_to_string(&path)
+                .map_err(|err| Error::new(format!("Couldn't read preset \"{}\": {}", path.display(), err)))?;
+            Ok((path.display().to_string(), data))
+        } else if let Some((_, preset)) = self.builtins.iter().find(|(builtin, _)| *builtin == name) {
+            Ok((format!("{} (built-in)", name), preset.to_string()))
+        } else {
+            Err(Error::new(format!("No preset named \"{}\" was found in \"{}\".", name, self.presets_dir().display())))
+        }
+    }
+
+    /// Parses a preset as a table, with the presets named by its `extends` key merged in first, in order.
+    ///
+    /// `loading` holds the presets being resolved further up, to catch presets that extend themselves.
+    fn resolve_preset(&self, source: &str, data: &str, loading: &mut Vec<String>) -> Result<Table>
+    where
+        P: Preset,
+    {
+        if loading.iter().any(|loaded| loaded == source) {
+            return Err(Error::new(format!("Preset \"{}\" extends itself.", source)));
+        }
+        let mut table = parse_table(source, data)?;
+        let parents = parents(source, &mut table)?;
+
+        let warnings = P::migrate(&mut table)
+            .map_err(|err| Error::new(format!("Invalid preset \"{}\": {}", source, err)))?;
+        for warning in warnings {
+            warn!("{}: {}", source, warning);
+        }
+
+        loading.push(source.to_string());
+        let mut resolved = Table::new();
+        for parent in parents {
+            let (parent_source, parent_data) = self.read_preset(&parent)?;
+            merge(&mut resolved, self.resolve_preset(&parent_source, &parent_data, loading)?, P::JOINED, "");
+        }
+        loading.pop();
+        merge(&mut resolved, table, P::JOINED, "");
+        Ok(resolved)
+    }
+
+    /// Lists the names of the presets in the presets folder along with the built-in ones, in alphabetical order.
+    pub fn presets(&self) -> Result<Vec<String>> {
+        let mut names = self.builtins.iter().map(|(name, _)| name.to_string()).collect::<Vec<_>>();
+        let presets = self.presets_dir();
+        if presets.exists() {
+            for entry in fs::read_dir(presets)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == "toml") {
+                    if let Some(name) = path.file_stem().and_then(|name| name.to_str()) {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+        }
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    /// Reads the paths from `config.toml`, if it exists.
+    pub fn paths(&self) -> Result<Option<Paths>> {
+        let file = self.config.join("config.toml");
+        if file.exists() {
+            Ok(Some(toml::from_slice::<Paths>(&fs::read(file)?).map_err(Error::new)?))
+        } else {
+            Ok(None)
+        }
     }
 
     pub fn get_or_create_paths<F>(&self, create: F) -> Result<Paths>
         where
             F: FnOnce() -> Result<Paths>,
     {
-        let file = self.config.join("config.toml");
-        if file.exists() {
-            Ok(toml::from_slice::<Paths>(&fs::read(file)?).map_err(Error::new)?)
+        if let Some(paths) = self.paths()? {
+            Ok(paths)
         } else {
+            let file = self.config.join("config.toml");
             info!("No config found at {}", file.to_path_buf().display());
             info!("Please enter configuration info:");
             let paths = create()?;
@@ -125,12 +310,18 @@ impl<P> System<P> {
 pub struct Paths {
     rom: PathBuf,
     output: PathBuf,
+    #[serde(default = "verify_rom_default")]
+    verify_rom: bool,
 }
 
 impl Paths {
     /// Generates new paths with the specified ROM and output directory.
     pub fn new(rom: PathBuf, output: PathBuf) -> Self {
-        Self { rom, output }
+        Self {
+            rom,
+            output,
+            verify_rom: verify_rom_default(),
+        }
     }
 
     /// Gets the path of the ROM file.
@@ -138,8 +329,237 @@ impl Paths {
         &self.rom
     }
 
+    /// Whether the ROM should be checked against the known dumps.
+    ///
+    /// On unless turned off. Formats without a known dump are let through with a warning.
+    pub fn verify_rom(&self) -> bool {
+        self.verify_rom
+    }
+
     /// Gets the output directory.
     pub fn output(&self) -> &Path {
         &self.output
     }
-}
\ No newline at end of file
+
+    /// Replaces the path of the ROM file.
+    pub fn set_rom(&mut self, rom: PathBuf) {
+        self.rom = rom;
+    }
+
+    /// Replaces the output directory.
+    pub fn set_output(&mut self, output: PathBuf) {
+        self.output = output;
+    }
+}
+
+fn verify_rom_default() -> bool {
+    true
+}
+
+/// Finds the config directory to use when none is given, as described on [`System::new`].
+fn config_dir() -> Result<PathBuf> {
+    if let Some(config) = env::var_os(CONFIG_DIR_VAR).filter(|config| !config.is_empty()) {
+        return Ok(PathBuf::from(config));
+    }
+    let local = env::current_dir()?;
+    if local.join("config.toml").exists() || local.join("presets").is_dir() {
+        return Ok(local);
+    }
+    ProjectDirs::from("", "", "z17-randomizer")
+        .map(|dirs| dirs.config_dir().to_path_buf())
+        .ok_or_else(|| {
+            Error::new(format!(
+                "Could not find a config directory. Set {} to the directory to use.",
+                CONFIG_DIR_VAR
+            ))
+        })
+}
+
+/// Parses a preset file as a table, without resolving the presets it extends.
+fn parse_table(source: &str, data: &str) -> Result<Table> {
+    toml::from_str::<Table>(data).map_err(|err| Error::new(format!("Invalid preset \"{}\": {}", source, err)))
+}
+
+/// Takes the names of the presets a preset extends out of its `extends` key.
+fn parents(source: &str, table: &mut Table) -> Result<Vec<String>> {
+    match table.remove("extends") {
+        None => Ok(Vec::new()),
+        Some(Value::String(name)) => Ok(vec![name]),
+        Some(Value::Array(names)) => names
+            .into_iter()
+            .map(|name| match name {
+                Value::String(name) => Ok(name),
+                _ => Err(()),
+            })
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| Error::new(format!("\"extends\" in \"{}\" must only list preset names.", source))),
+        Some(_) => Err(Error::new(format!(
+            "\"extends\" in \"{}\" must be a preset name or a list of them.",
+            source
+        ))),
+    }
+}
+
+/// Serializes a preset as a table.
+fn to_table<T>(value: &T) -> Result<Table>
+where
+    T: Serialize,
+{
+    match Value::try_from(value).map_err(Error::new)? {
+        Value::Table(table) => Ok(table),
+        _ => Err(Error::new("A preset must be a table.")),
+    }
+}
+
+/// Merges a preset into the ones it extends.
+///
+/// Tables are merged key by key, and the lists at the `joined` paths are joined without duplicates, so exclusions and
+/// rules add up. Any other value, other lists included, replaces the inherited one.
+fn merge(base: &mut Table, overlay: Table, joined: &[&str], path: &str) {
+    for (key, value) in overlay {
+        let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(value)) => merge(base, value, joined, &path),
+            (Some(Value::Array(base)), Value::Array(value)) if joined.contains(&path.as_str()) => {
+                for item in value {
+                    if !base.contains(&item) {
+                        base.push(item);
+                    }
+                }
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Undoes [`merge`], removing everything a preset shares with the ones it extends.
+///
+/// Fails if an item is missing from one of the `joined` lists, since a preset can only add to those.
+fn strip(table: &mut Table, inherited: &Table, joined: &[&str], path: &str) -> Result<()> {
+    let keys = table.keys().cloned().collect::<Vec<_>>();
+    for key in keys {
+        let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+        let inherited = match inherited.get(&key) {
+            Some(inherited) => inherited,
+            None => continue,
+        };
+        let shared = match (table.get_mut(&key).unwrap(), inherited) {
+            (Value::Table(table), Value::Table(inherited)) => {
+                strip(table, inherited, joined, &path)?;
+                table.is_empty()
+            }
+            (Value::Array(items), Value::Array(inherited)) if joined.contains(&path.as_str()) => {
+                if let Some(missing) = inherited.iter().find(|item| !items.contains(item)) {
+                    return Err(Error::new(format!(
+                        "{} comes from a preset this one extends, so it can't be removed from {}.",
+                        missing, path
+                    )));
+                }
+                items.retain(|item| !inherited.contains(item));
+                items.is_empty()
+            }
+            (value, inherited) => value == inherited,
+        };
+        if shared {
+            table.remove(&key);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize, Serialize)]
+    struct Test {
+        name: String,
+        #[serde(default)]
+        list: Vec<String>,
+    }
+
+    impl Preset for Test {
+        const JOINED: &'static [&'static str] = &["list"];
+
+        fn migrate(_: &mut Table) -> Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+    }
+
+    const BUILTINS: &[(&str, &str)] = &[("Builtin", "name = \"builtin\"\nlist = [\"a\"]")];
+
+    #[test]
+    fn it_finds_builtin_presets() {
+        let config = env::temp_dir().join(format!("z17r-sys-test-{}", std::process::id()));
+        let system = System::<Test>::new(Some(config.clone()), BUILTINS).unwrap();
+        assert!(config.join("presets/Builtin.toml").exists());
+
+        // Deleted built-in presets stay available, and files take precedence over them
+        fs::remove_file(config.join("presets/Builtin.toml")).unwrap();
+        assert_eq!(system.preset("Builtin").unwrap().name, "builtin");
+        fs::write(config.join("presets/Custom.toml"), "extends = \"Builtin\"\nname = \"custom\"\nlist = [\"b\"]").unwrap();
+        let custom = system.preset("Custom").unwrap();
+        assert_eq!(custom.name, "custom");
+        assert_eq!(custom.list, ["a", "b"]);
+        assert_eq!(system.presets().unwrap(), ["Builtin", "Custom"]);
+        assert!(system.preset("Missing").is_err());
+
+        fs::remove_dir_all(config).unwrap();
+    }
+
+    #[test]
+    fn it_saves_only_changes() {
+        let config = env::temp_dir().join(format!("z17r-sys-save-test-{}", std::process::id()));
+        let system = System::<Test>::new(Some(config.clone()), BUILTINS).unwrap();
+        let extends = ["Builtin".to_string()];
+
+        let custom = Test { name: "custom".to_string(), list: vec!["a".to_string(), "b".to_string()] };
+        let path = system.save_preset("Custom", &custom, &extends).unwrap();
+        let saved = toml::from_str::<Table>(&fs::read_to_string(path).unwrap()).unwrap();
+        let expected = toml::from_str::<Table>("extends = [\"Builtin\"]\nname = \"custom\"\nlist = [\"b\"]").unwrap();
+        assert_eq!(saved, expected);
+        assert_eq!(system.preset("Custom").unwrap().list, ["a", "b"]);
+        assert_eq!(system.extends("Custom").unwrap(), extends);
+
+        // Inherited items can't be taken out of joined lists
+        let removed = Test { name: "custom".to_string(), list: vec!["b".to_string()] };
+        assert!(system.save_preset("Custom", &removed, &extends).is_err());
+
+        fs::remove_dir_all(config).unwrap();
+    }
+
+    #[test]
+    fn it_merges_presets() {
+        let mut base = toml::from_str::<Table>(
+            "[logic]\nmode = \"Normal\"\nbell_in_shop = true\n[exclusions]\nexclusions = [\"Dampe\", \"Rosso\"]",
+        )
+        .unwrap();
+        let overlay = toml::from_str::<Table>(
+            "[logic]\nmode = \"Hard\"\n[exclusions]\nexclusions = [\"Rosso\", \"Blacksmith\"]",
+        )
+        .unwrap();
+        merge(&mut base, overlay, &["exclusions.exclusions"], "");
+        let expected = toml::from_str::<Table>(
+            "[logic]\nmode = \"Hard\"\nbell_in_shop = true\n[exclusions]\nexclusions = [\"Dampe\", \"Rosso\", \"Blacksmith\"]",
+        )
+        .unwrap();
+        assert_eq!(base, expected);
+    }
+
+    #[test]
+    fn it_replaces_other_lists() {
+        let mut base = toml::from_str::<Table>(
+            "rules = [\"a\"]\n[options]\nlanguages = [\"English\", \"French\"]",
+        )
+        .unwrap();
+        let overlay = toml::from_str::<Table>("rules = [\"b\"]\n[options]\nlanguages = [\"Spanish\"]").unwrap();
+        merge(&mut base, overlay, &["rules"], "");
+        let expected = toml::from_str::<Table>(
+            "rules = [\"a\", \"b\"]\n[options]\nlanguages = [\"Spanish\"]",
+        )
+        .unwrap();
+        assert_eq!(base, expected);
+    }
+}